@@ -0,0 +1,9 @@
+-- One row per login, keyed by the session_id minted in create_token
+ALTER TABLE sessions
+    ADD COLUMN user_agent VARCHAR(255) NOT NULL DEFAULT 'unknown',
+    ADD COLUMN ip_address VARCHAR(45) NULL,
+    ADD COLUMN created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN last_used_at DATETIME NULL;
+
+CREATE UNIQUE INDEX idx_sessions_refresh_token ON sessions (refresh_token);
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
@@ -0,0 +1,12 @@
+-- Revoked sessions can no longer mint access tokens
+ALTER TABLE sessions
+    ADD COLUMN is_revoke BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Refresh tokens that were already swapped for a new one, kept to detect reuse
+CREATE TABLE rotated_refresh_tokens (
+    refresh_token VARCHAR(64) NOT NULL PRIMARY KEY,
+    session_id VARCHAR(32) NOT NULL,
+    rotated_at DATETIME NOT NULL,
+    INDEX idx_rotated_refresh_tokens_session_id (session_id),
+    FOREIGN KEY (session_id) REFERENCES sessions (session_id) ON DELETE CASCADE
+);
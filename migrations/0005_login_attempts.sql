@@ -0,0 +1,9 @@
+-- Failed login counters per username and per IP address
+CREATE TABLE login_attempts (
+    scope VARCHAR(16) NOT NULL,
+    identifier VARCHAR(255) NOT NULL,
+    failed_count INT NOT NULL,
+    last_failed_at DATETIME NOT NULL,
+    locked_until DATETIME NULL,
+    PRIMARY KEY (scope, identifier)
+);
@@ -0,0 +1,6 @@
+-- Access tokens revoked before their exp, rows can be dropped once expires_at has passed
+CREATE TABLE revoked_access_tokens (
+    jti VARCHAR(36) PRIMARY KEY,
+    expires_at DATETIME NOT NULL,
+    INDEX idx_revoked_access_tokens_expires_at (expires_at)
+);
@@ -0,0 +1,3 @@
+-- Copied into every access token, bumping it invalidates the tokens already issued
+ALTER TABLE users
+    ADD COLUMN token_version INT NOT NULL DEFAULT 0;
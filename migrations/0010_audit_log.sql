@@ -0,0 +1,12 @@
+-- Who changed what and when for sensitive account changes
+CREATE TABLE audit_log (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    actor_id INT NULL,
+    target_user_id INT NOT NULL,
+    action VARCHAR(50) NOT NULL,
+    old_value VARCHAR(255) NULL,
+    new_value VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    INDEX idx_audit_log_target_user_id (target_user_id),
+    FOREIGN KEY (actor_id) REFERENCES users (id) ON DELETE SET NULL
+);
@@ -0,0 +1,23 @@
+-- External identities linked to users, one row per (issuer, subject)
+CREATE TABLE user_identities (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255) NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE INDEX idx_user_identities_issuer_subject (issuer, subject),
+    INDEX idx_user_identities_user_id (user_id),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- Pending authorization requests, consumed by the callback. link_user_id is set when
+-- a logged in user links an identity instead of logging in.
+CREATE TABLE oidc_login_states (
+    state_hash CHAR(64) PRIMARY KEY,
+    pkce_verifier VARCHAR(128) NOT NULL,
+    nonce VARCHAR(128) NOT NULL,
+    link_user_id INT NULL,
+    expires_at DATETIME NOT NULL,
+    FOREIGN KEY (link_user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,15 @@
+-- Single-use invitation codes, only the SHA-256 hash of the code is stored
+CREATE TABLE invitations (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    code_hash CHAR(64) NOT NULL,
+    role VARCHAR(20) NOT NULL,
+    created_by INT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME NULL,
+    used_by INT NULL,
+    revoked_at DATETIME NULL,
+    UNIQUE INDEX idx_invitations_code_hash (code_hash),
+    FOREIGN KEY (created_by) REFERENCES users (id) ON DELETE SET NULL,
+    FOREIGN KEY (used_by) REFERENCES users (id) ON DELETE SET NULL
+);
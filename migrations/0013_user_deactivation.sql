@@ -0,0 +1,6 @@
+-- Users are deactivated instead of deleted so their task and group history stays intact
+ALTER TABLE users
+    ADD COLUMN is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    ADD COLUMN deleted_at DATETIME NULL;
+
+CREATE INDEX idx_users_is_active ON users (is_active);
@@ -0,0 +1,3 @@
+-- Last TOTP time step accepted for the user, codes from this step or earlier are rejected
+ALTER TABLE users
+    ADD COLUMN totp_last_step BIGINT NULL;
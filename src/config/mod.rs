@@ -1 +1,8 @@
-pub mod mysql;
\ No newline at end of file
+pub mod mysql;
+pub mod origin;
+pub mod totp;
+pub mod jwt;
+pub mod oidc;
+pub mod password;
+pub mod profile_picture;
+pub mod proxy;
\ No newline at end of file
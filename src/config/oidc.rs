@@ -0,0 +1,30 @@
+use std::{env, sync::LazyLock};
+
+// Campus identity provider, OIDC login is disabled while OIDC_ISSUER_URL is unset
+pub struct OidcSettings {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: Vec<String>,
+}
+
+static OIDC_SETTINGS: LazyLock<Option<OidcSettings>> = LazyLock::new(|| {
+    let issuer_url = env::var("OIDC_ISSUER_URL").ok()?;
+
+    Some(OidcSettings {
+        issuer_url,
+        client_id: env::var("OIDC_CLIENT_ID").expect("OIDC_CLIENT_ID must be set"),
+        client_secret: env::var("OIDC_CLIENT_SECRET").ok(),
+        redirect_url: env::var("OIDC_REDIRECT_URL").expect("OIDC_REDIRECT_URL must be set"),
+        scopes: env::var("OIDC_SCOPES")
+            .unwrap_or_else(|_| "email profile".to_string())
+            .split_whitespace()
+            .map(|scope| scope.to_string())
+            .collect(),
+    })
+});
+
+pub fn oidc_settings() -> Option<&'static OidcSettings> {
+    OIDC_SETTINGS.as_ref()
+}
@@ -0,0 +1,20 @@
+use std::{env, sync::LazyLock};
+
+// Frontend origins allowed by CORS and by the CSRF check on cookie authentication
+static ALLOWED_ORIGINS: LazyLock<Vec<String>> = LazyLock::new(|| {
+    env::var("ALLOWED_ORIGINS")
+        .unwrap_or_else(|_| "http://localhost:3000".to_string())
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect()
+});
+
+pub fn allowed_origins() -> &'static [String] {
+    &ALLOWED_ORIGINS
+}
+
+pub fn is_allowed_origin(origin: &str) -> bool {
+    let origin = origin.trim_end_matches('/');
+    ALLOWED_ORIGINS.iter().any(|allowed| allowed == origin)
+}
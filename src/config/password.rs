@@ -0,0 +1,41 @@
+use std::{env, sync::LazyLock};
+
+use argon2::Params;
+
+use super::jwt::env_number;
+
+// Argon2id cost, e.g. ARGON2_MEMORY_KIB=65536 ARGON2_ITERATIONS=3 ARGON2_PARALLELISM=1
+static ARGON2_PARAMS: LazyLock<Params> = LazyLock::new(|| {
+    Params::new(
+        env_number("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+        env_number("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+        env_number("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+        None,
+    )
+    .unwrap_or_else(|e| panic!("Invalid Argon2 parameters: {}", e))
+});
+
+pub fn argon2_params() -> &'static Params {
+    &ARGON2_PARAMS
+}
+
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub reject_username: bool,
+    pub reject_common: bool,
+}
+
+// e.g. PASSWORD_MIN_LENGTH=10 PASSWORD_REJECT_USERNAME=true PASSWORD_REJECT_COMMON=true
+static PASSWORD_POLICY: LazyLock<PasswordPolicy> = LazyLock::new(|| PasswordPolicy {
+    min_length: env_number("PASSWORD_MIN_LENGTH", 8),
+    reject_username: env::var("PASSWORD_REJECT_USERNAME")
+        .map(|value| value != "false")
+        .unwrap_or(true),
+    reject_common: env::var("PASSWORD_REJECT_COMMON")
+        .map(|value| value != "false")
+        .unwrap_or(true),
+});
+
+pub fn password_policy() -> &'static PasswordPolicy {
+    &PASSWORD_POLICY
+}
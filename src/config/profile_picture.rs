@@ -0,0 +1,20 @@
+use std::sync::LazyLock;
+
+use super::jwt::env_number;
+
+// Upload limits, e.g. PROFILE_PICTURE_MAX_BYTES=2097152 PROFILE_PICTURE_MAX_DIMENSION=4096
+pub struct ProfilePictureSettings {
+    pub max_bytes: usize,
+    // Bigger images are rejected before decoding, a small file can still be a huge image
+    pub max_dimension: u32,
+}
+
+static PROFILE_PICTURE_SETTINGS: LazyLock<ProfilePictureSettings> =
+    LazyLock::new(|| ProfilePictureSettings {
+        max_bytes: env_number("PROFILE_PICTURE_MAX_BYTES", 2 * 1024 * 1024),
+        max_dimension: env_number("PROFILE_PICTURE_MAX_DIMENSION", 4096),
+    });
+
+pub fn profile_picture_settings() -> &'static ProfilePictureSettings {
+    &PROFILE_PICTURE_SETTINGS
+}
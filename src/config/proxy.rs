@@ -0,0 +1,86 @@
+use std::{env, net::IpAddr, sync::LazyLock};
+
+// Reverse proxies whose X-Forwarded-For is believed, e.g. TRUSTED_PROXIES=127.0.0.1,10.0.0.2.
+// Without it the TCP peer address is used, clients can put anything in forwarded headers.
+static TRUSTED_PROXIES: LazyLock<Vec<IpAddr>> = LazyLock::new(|| {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|proxy| proxy.trim())
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            proxy
+                .parse()
+                .unwrap_or_else(|e| panic!("Invalid TRUSTED_PROXIES entry {}: {}", proxy, e))
+        })
+        .collect()
+});
+
+// Parse the list at startup so a bad entry fails before serving requests
+pub fn init_trusted_proxies() {
+    LazyLock::force(&TRUSTED_PROXIES);
+}
+
+// Address of the client behind any trusted proxies in front of the server
+pub fn client_ip(peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+    resolve_client_ip(peer, forwarded_for, &TRUSTED_PROXIES)
+}
+
+// Each proxy appends the address it received the request from, so X-Forwarded-For is walked
+// from the right and the first address that isn't a trusted proxy is the client
+fn resolve_client_ip(peer: IpAddr, forwarded_for: Option<&str>, trusted: &[IpAddr]) -> IpAddr {
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+
+    for entry in forwarded_for.unwrap_or_default().rsplit(',') {
+        match entry.trim().parse::<IpAddr>() {
+            Ok(ip) if trusted.contains(&ip) => client = ip,
+            Ok(ip) => return ip,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_forwarded_headers_from_untrusted_peers() {
+        let trusted = [ip("10.0.0.2")];
+
+        assert_eq!(
+            resolve_client_ip(ip("203.0.113.9"), Some("198.51.100.1"), &trusted),
+            ip("203.0.113.9")
+        );
+        assert_eq!(
+            resolve_client_ip(ip("203.0.113.9"), Some("198.51.100.1"), &[]),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn takes_the_rightmost_untrusted_forwarded_address() {
+        let trusted = [ip("10.0.0.2"), ip("10.0.0.3")];
+
+        // The spoofed left-most entry is never reached
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some("1.2.3.4, 198.51.100.1, 10.0.0.3"), &trusted),
+            ip("198.51.100.1")
+        );
+        assert_eq!(resolve_client_ip(ip("10.0.0.2"), None, &trusted), ip("10.0.0.2"));
+        assert_eq!(
+            resolve_client_ip(ip("10.0.0.2"), Some("1.2.3.4, garbage"), &trusted),
+            ip("10.0.0.2")
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use std::{env, sync::LazyLock};
+
+use crate::models::users::Role;
+
+// Roles that must enrol TOTP before using the API, e.g. TOTP_REQUIRED_ROLES=Ketua,Sekretaris
+static REQUIRED_ROLES: LazyLock<Vec<String>> = LazyLock::new(|| {
+    env::var("TOTP_REQUIRED_ROLES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|role| role.trim().to_string())
+        .filter(|role| !role.is_empty())
+        .collect()
+});
+
+static ISSUER: LazyLock<String> =
+    LazyLock::new(|| env::var("TOTP_ISSUER").unwrap_or_else(|_| "Task Management".to_string()));
+
+pub fn is_totp_required(role: &Role) -> bool {
+    let role = role.to_string();
+    REQUIRED_ROLES.contains(&role)
+}
+
+pub fn totp_issuer() -> &'static str {
+    &ISSUER
+}
@@ -1,46 +1,93 @@
-use std::env;
-
-use actix_web::{cookie::{time::Duration, Cookie}, web, Responder};
+use actix_web::{cookie::{time::Duration, Cookie}, web, HttpRequest, HttpResponse, Responder};
 use sqlx::MySqlPool;
 
 use crate::{
-    models::{auth::LoginRequest, message::ErrorMessage},
-    utils::{jwt::create_token, responder::ApiResponder, security::verify_password},
+    controllers::{
+        session::{current_session, revoke_session, revoke_user_sessions},
+        two_factor::fetch_totp_state_by_username,
+    },
+    models::{
+        auth::{LoginRequest, Token},
+        message::ErrorMessage,
+        session::{ClientInfo, RevokeSessionRequest},
+    },
+    utils::{
+        jwt::{create_challenge_token, create_token, request_claims, revoke_access_token},
+        responder::ApiResponder,
+        security::{defer_rehash_if_outdated, rehash_password_if_outdated, verify_password},
+        throttle::{
+            IP_POLICY, USERNAME_POLICY, clear_failed_logins, release_login_attempt,
+            reserve_login_attempt,
+        },
+        token_version::forget_token_version,
+    },
 };
 
 pub async fn login_handler(
     pool: web::Data<MySqlPool>,
+    req: HttpRequest,
     data: web::Json<LoginRequest>,
 ) -> impl Responder {
     let login = data.into_inner();
+    let client = ClientInfo::from_request(&req);
+    let ip_address = client.ip_address.clone().unwrap_or_default();
 
-    let is_valid = verify_password(&pool, &login.username, &login.password).await;
+    // Both attempts are counted as failed up front and given back once the password matches
+    let locked = match reserve_login_attempt(&pool, &IP_POLICY, &ip_address).await {
+        Ok(None) => match reserve_login_attempt(&pool, &USERNAME_POLICY, &login.username).await {
+            Ok(None) => None,
+            Ok(locked) => {
+                if let Err(e) = release_login_attempt(&pool, &IP_POLICY, &ip_address).await {
+                    return ApiResponder::<()>::handle_error(e);
+                }
+                locked
+            }
+            Err(e) => return ApiResponder::<()>::handle_error(e),
+        },
+        Ok(locked) => locked,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
 
-    let token = create_token(&pool, &env::var("SECRET_KEY").unwrap(), &login.username).await;
+    if let Some(retry_after_secs) = locked {
+        return ApiResponder::too_many_requests(
+            ErrorMessage::LoginThrottled.to_string(),
+            None::<()>,
+            retry_after_secs,
+        );
+    }
+
+    let is_valid = verify_password(&pool, &login.username, &login.password).await;
 
     if is_valid {
-        match token {
-            Ok(token) => {
-                let access_cookie = Cookie::build("access_token", token.access_token.clone())
-                    .http_only(true)
-                    .secure(false) 
-                    .path("/")
-                    .finish();
-
-                let refresh_cookie = Cookie::build("refresh_token", token.refresh_token.clone())
-                    .http_only(true)
-                    .secure(false)
-                    .path("/")
-                    .finish();
-
-                let cookies = vec![access_cookie, refresh_cookie];
-
-                ApiResponder::success_with_cookie(
-                    ErrorMessage::LoginSuccess.to_string(),
-                    Some(serde_json::json!({ "token": token.access_token })),
-                    cookies,
-                )
+        if let Err(e) = release_login_attempt(&pool, &IP_POLICY, &ip_address).await {
+            return ApiResponder::<()>::handle_error(e);
+        }
+
+        // Earlier failures are kept until the TOTP code is verified so guesses stay throttled
+        if let Some(challenge) = two_factor_challenge(&pool, &login.username).await {
+            if let Err(e) = release_login_attempt(&pool, &USERNAME_POLICY, &login.username).await {
+                return ApiResponder::<()>::handle_error(e);
+            }
+
+            if let Err(e) = defer_rehash_if_outdated(&pool, &login.username, &login.password).await {
+                tracing::warn!("Failed to rehash password of {}: {}", login.username, e);
             }
+
+            return challenge;
+        }
+
+        if let Err(e) = clear_failed_logins(&pool, &USERNAME_POLICY, &login.username).await {
+            return ApiResponder::<()>::handle_error(e);
+        }
+
+        if let Err(e) = rehash_password_if_outdated(&pool, &login.username, &login.password).await {
+            tracing::warn!("Failed to rehash password of {}: {}", login.username, e);
+        }
+
+        let token = create_token(&pool, &login.username, &client).await;
+
+        match token {
+            Ok(token) => token_response(token),
             Err(e) => ApiResponder::unauthorized(
                 ErrorMessage::TokenGenerateFailed {
                     details: e.to_string(),
@@ -54,7 +101,127 @@ pub async fn login_handler(
     }
 }
 
-pub async fn logout_handler() -> impl Responder {
+// With TOTP enabled the first factor only earns a challenge token, None when no TOTP is needed
+pub async fn two_factor_challenge(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+) -> Option<HttpResponse> {
+    match fetch_totp_state_by_username(pool, username).await {
+        Ok(Some(state)) if state.totp_enabled => {
+            Some(match create_challenge_token(state.id, &state.username) {
+                Ok(challenge_token) => ApiResponder::success(
+                    ErrorMessage::TwoFactorRequired.to_string(),
+                    Some(serde_json::json!({
+                        "two_factor_required": true,
+                        "challenge_token": challenge_token,
+                    })),
+                ),
+                Err(e) => ApiResponder::unauthorized(
+                    ErrorMessage::TokenGenerateFailed {
+                        details: e.to_string(),
+                    }
+                    .to_string(),
+                    None::<()>,
+                ),
+            })
+        }
+        Ok(_) => None,
+        Err(e) => Some(ApiResponder::<()>::handle_error(e)),
+    }
+}
+
+// Sets the token cookies and returns the access token for clients using the header
+pub fn token_response(token: Token) -> HttpResponse {
+    let access_cookie = Cookie::build("access_token", token.access_token.clone())
+        .http_only(true)
+        .secure(false)
+        .path("/")
+        .finish();
+
+    let refresh_cookie = Cookie::build("refresh_token", token.refresh_token.clone())
+        .http_only(true)
+        .secure(false)
+        .path("/")
+        .finish();
+
+    let cookies = vec![access_cookie, refresh_cookie];
+
+    ApiResponder::success_with_cookie(
+        ErrorMessage::LoginSuccess.to_string(),
+        Some(serde_json::json!({
+            "token": token.access_token,
+            "must_change_password": token.must_change_password,
+        })),
+        cookies,
+    )
+}
+
+// Logout from the current device, the session can no longer renew tokens
+pub async fn logout_handler(pool: web::Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let session = match current_session(&pool, &req).await {
+        Ok(session) => session,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if let Some((session_id, _)) = session {
+        let request = RevokeSessionRequest {
+            session_id,
+            is_revoke: true,
+        };
+
+        if let Err(e) = revoke_session(&pool, &request).await {
+            return ApiResponder::<()>::handle_error(e);
+        }
+    }
+
+    if let Err(e) = revoke_current_access_token(&pool, &req).await {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    logout_response()
+}
+
+// Logout from every device by revoking all sessions of the user
+pub async fn logout_all_handler(pool: web::Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let user_id = match current_session(&pool, &req).await {
+        Ok(Some((_, user_id))) => user_id,
+        Ok(None) => {
+            return ApiResponder::unauthorized(ErrorMessage::UnAuthorized.to_string(), None::<()>);
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if let Err(e) = revoke_user_sessions(&pool, user_id).await {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    // Access tokens already issued to the other devices are rejected by the newer version
+    let bumped = sqlx::query(r"UPDATE users SET token_version = token_version + 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(pool.get_ref())
+        .await;
+
+    if let Err(e) = bumped {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    forget_token_version(user_id);
+
+    logout_response()
+}
+
+// The access token stays valid until it expires unless its jti is deny-listed
+async fn revoke_current_access_token(
+    pool: &web::Data<MySqlPool>,
+    req: &HttpRequest,
+) -> Result<(), sqlx::Error> {
+    match request_claims(pool, req).await {
+        Ok(claims) => revoke_access_token(pool, &claims).await,
+        Err(_) => Ok(()),
+    }
+}
+
+fn logout_response() -> HttpResponse {
     let access_cookie = Cookie::build("access_token", "")
         .path("/")
         .http_only(true)
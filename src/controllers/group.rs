@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use actix_web::{HttpRequest, Responder, web};
+use actix_web::{Responder, web};
 use chrono::NaiveDateTime;
 use sqlx::MySqlPool;
 
@@ -11,148 +11,108 @@ use crate::{
             RemoveMemberRequest, UserDetail,
         },
         message::ErrorMessage,
-        users::Role,
     },
-    utils::{jwt::extract_claims, responder::ApiResponder},
+    utils::responder::ApiResponder,
 };
 
 // Create Group to database
 pub async fn create_grup(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     request: web::Json<CreateGroupRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
+    let max_query = r"SELECT MAX(group_number) as last_number FROM `groups` WHERE course = ?";
+
+    let last_number: Option<i32> = match sqlx::query_scalar(max_query)
+        .bind(&request.course)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(num) => num,
+        Err(_) => Some(0),
     };
 
-    if Role::has_permission(&claims.role) {
-        let max_query = r"SELECT MAX(group_number) as last_number FROM `groups` WHERE course = ?";
+    let next_group_number = last_number.unwrap_or(0) + 1;
 
-        let last_number: Option<i32> = match sqlx::query_scalar(max_query)
-            .bind(&request.course)
-            .fetch_one(pool.get_ref())
-            .await
-        {
-            Ok(num) => num,
-            Err(_) => Some(0),
-        };
+    let insert_query = r"INSERT INTO `groups` (course, group_number) VALUES (?, ?)";
 
-        let next_group_number = last_number.unwrap_or(0) + 1;
+    let result = sqlx::query(insert_query)
+        .bind(&request.course)
+        .bind(next_group_number)
+        .execute(pool.get_ref())
+        .await;
 
-        let insert_query = r"INSERT INTO `groups` (course, group_number) VALUES (?, ?)";
-
-        let result = sqlx::query(insert_query)
-            .bind(&request.course)
-            .bind(next_group_number)
-            .execute(pool.get_ref())
-            .await;
-
-        match result {
-            Ok(res) => {
-                let inserted_id = res.last_insert_id();
-                ApiResponder::success(
-                    ErrorMessage::Success.to_string(),
-                    Some(CreateGroupResponse {
-                        id: inserted_id as i32,
-                        course: request.course.clone(),
-                        members: Vec::new(),
-                        group_number: next_group_number,
-                    }),
-                )
-            }
-            Err(e) => ApiResponder::<()>::handle_error(e),
+    match result {
+        Ok(res) => {
+            let inserted_id = res.last_insert_id();
+            ApiResponder::success(
+                ErrorMessage::Success.to_string(),
+                Some(CreateGroupResponse {
+                    id: inserted_id as i32,
+                    course: request.course.clone(),
+                    members: Vec::new(),
+                    group_number: next_group_number,
+                }),
+            )
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
 // Add multi user to group
 pub async fn add_member_to_group(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     group_id: web::Path<i32>,
     users_id: web::Json<AddMembersRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
-
-    if Role::has_permission(&claims.role) {
-        let mut query = String::from("INSERT INTO group_members (group_id, user_id) VALUES ");
-        let group_id = group_id.into_inner();
-
-        query.push_str(
-            &users_id
-                .users
-                .iter()
-                .enumerate()
-                .map(|(i, _)| if i > 0 { ", (?, ?)" } else { "(?, ?)" })
-                .collect::<String>(),
-        );
-
-        let mut sql_query = sqlx::query(&query);
-
-        for user_id in &users_id.users {
-            sql_query = sql_query.bind(group_id).bind(user_id);
-        }
+    let mut query = String::from("INSERT INTO group_members (group_id, user_id) VALUES ");
+    let group_id = group_id.into_inner();
+
+    query.push_str(
+        &users_id
+            .users
+            .iter()
+            .enumerate()
+            .map(|(i, _)| if i > 0 { ", (?, ?)" } else { "(?, ?)" })
+            .collect::<String>(),
+    );
+
+    let mut sql_query = sqlx::query(&query);
+
+    for user_id in &users_id.users {
+        sql_query = sql_query.bind(group_id).bind(user_id);
+    }
 
-        let result = sql_query.execute(pool.get_ref()).await;
+    let result = sql_query.execute(pool.get_ref()).await;
 
-        match result {
-            Ok(_) => ApiResponder::success(ErrorMessage::Success.to_string(), None::<()>),
-            Err(e) => ApiResponder::<()>::handle_error(e),
-        }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+    match result {
+        Ok(_) => ApiResponder::success(ErrorMessage::Success.to_string(), None::<()>),
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
 // Delete spesific members to from group
 pub async fn remove_member_from_group(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     group_id: web::Path<i32>,
     request: web::Json<RemoveMemberRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
-
-    if Role::has_permission(&claims.role) {
-        let query = r"Delete from group_members WHERE user_id = ? && group_id = ?";
-
-        let result = sqlx::query(query)
-            .bind(request.user_id)
-            .bind(*group_id)
-            .execute(pool.get_ref())
-            .await;
-
-        match result {
-            Ok(e) => {
-                if e.rows_affected() == 0 {
-                    ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
-                } else {
-                    ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
-                }
+    let query = r"Delete from group_members WHERE user_id = ? && group_id = ?";
+
+    let result = sqlx::query(query)
+        .bind(request.user_id)
+        .bind(*group_id)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(e) => {
+            if e.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
             }
-            Err(e) => ApiResponder::<()>::handle_error(e),
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
@@ -231,36 +191,23 @@ pub async fn get_all_groups(pool: web::Data<MySqlPool>) -> impl Responder {
 // Delete groups from database
 pub async fn delete_group(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     group_id: web::Path<i32>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
-
-    if Role::has_permission(&claims.role) {
-        let query = r"DELETE FROM `groups` where id = ?";
-
-        let result = sqlx::query(query)
-            .bind(*group_id)
-            .execute(pool.get_ref())
-            .await;
-
-        match result {
-            Ok(e) => {
-                if e.rows_affected() == 0 {
-                    ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
-                } else {
-                    ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
-                }
+    let query = r"DELETE FROM `groups` where id = ?";
+
+    let result = sqlx::query(query)
+        .bind(*group_id)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(e) => {
+            if e.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
             }
-            Err(e) => ApiResponder::<()>::handle_error(e),
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
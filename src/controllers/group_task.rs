@@ -73,8 +73,8 @@ pub async fn add_finished_group_task(
     let query = r"INSERT INTO finished_group_tasks (task_id, group_id) VALUES (?, ?)";
 
     let response = sqlx::query(query)
-        .bind(&req_data.task_id)
-        .bind(&req_data.group_id)
+        .bind(req_data.task_id)
+        .bind(req_data.group_id)
         .execute(pool.get_ref())
         .await;
 
@@ -0,0 +1,213 @@
+use actix_web::{Responder, web};
+use chrono::Duration;
+use nanoid::nanoid;
+use sqlx::MySqlPool;
+
+use crate::{
+    middleware::auth::AuthUser,
+    models::{
+        invitation::{
+            CreateInvitationRequest, CreatedInvitationResponse, InvitationResponse,
+            RedeemInvitationRequest,
+        },
+        message::ErrorMessage,
+        permission::Permission,
+        users::{Role, UserResponse},
+    },
+    utils::{
+        responder::ApiResponder,
+        password_policy::validate_password,
+        security::{hash_password, hash_token},
+    },
+};
+
+const DEFAULT_EXPIRES_IN_DAYS: i64 = 7;
+const MAX_EXPIRES_IN_DAYS: i64 = 365;
+
+// Create an invitation, the code is only returned in this response
+pub async fn create_invitation(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    data: web::Json<CreateInvitationRequest>,
+) -> impl Responder {
+    let request = data.into_inner();
+
+    // Inviting a Ketua hands out Ketua rights, only allowed for who may change roles
+    if matches!(request.role, Role::Ketua) && !claims.has_permission(Permission::UserChangeRole) {
+        return ApiResponder::forbidden(
+            ErrorMessage::InsufficientPermissions.to_string(),
+            None::<()>,
+        );
+    }
+
+    let expires_in_days = request.expires_in_days.unwrap_or(DEFAULT_EXPIRES_IN_DAYS);
+
+    if !(1..=MAX_EXPIRES_IN_DAYS).contains(&expires_in_days) {
+        return ApiResponder::bad_request(
+            ErrorMessage::Error {
+                details: format!("expires_in_days must be between 1 and {}", MAX_EXPIRES_IN_DAYS),
+            }
+            .to_string(),
+            None::<()>,
+        );
+    }
+
+    let code = nanoid!(24);
+    let expires_at = (chrono::Utc::now() + Duration::days(expires_in_days)).naive_utc();
+
+    let query = r"INSERT INTO invitations (code_hash, role, created_by, expires_at) VALUES (?, ?, ?, ?)";
+
+    let result = sqlx::query(query)
+        .bind(hash_token(&code))
+        .bind(request.role.to_string())
+        .bind(claims.user_id)
+        .bind(expires_at)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(res) => ApiResponder::created(
+            ErrorMessage::CreateDataSuccess.to_string(),
+            Some(CreatedInvitationResponse {
+                id: res.last_insert_id() as i32,
+                code,
+                role: request.role,
+                expires_at,
+            }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// List every invitation, newest first
+pub async fn get_all_invitations(pool: web::Data<MySqlPool>) -> impl Responder {
+    let query = r"SELECT id, role, created_by, created_at, expires_at, used_at, used_by, revoked_at
+                  FROM invitations ORDER BY created_at DESC";
+
+    let result = sqlx::query_as::<_, InvitationResponse>(query)
+        .fetch_all(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(data) => ApiResponder::success(ErrorMessage::Success.to_string(), Some(data)),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Revoke an invitation that has not been redeemed yet
+pub async fn revoke_invitation(pool: web::Data<MySqlPool>, id: web::Path<i32>) -> impl Responder {
+    let result = sqlx::query(
+        r"UPDATE invitations SET revoked_at = ?
+          WHERE id = ? AND used_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(*id)
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(res) => {
+            if res.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
+            }
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Create an account from an invitation, the invitation and the new user are committed together
+pub async fn redeem_invitation(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<RedeemInvitationRequest>,
+) -> impl Responder {
+    let request = data.into_inner();
+    let now = chrono::Utc::now().naive_utc();
+
+    let errors = validate_password("password", &request.password, &request.username);
+
+    if !errors.is_empty() {
+        return ApiResponder::unprocessable_entity(
+            ErrorMessage::PasswordPolicyViolation.to_string(),
+            Some(errors),
+        );
+    }
+
+    let hashed_password = match hash_password(&request.password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::PasswordHashFailed {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let query = r"SELECT id, role FROM invitations
+                  WHERE code_hash = ? AND used_at IS NULL AND revoked_at IS NULL AND expires_at > ?
+                  FOR UPDATE";
+
+    let (invitation_id, role) = match sqlx::query_as::<_, (i32, String)>(query)
+        .bind(hash_token(&request.code))
+        .bind(now)
+        .fetch_optional(&mut tx)
+        .await
+    {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => {
+            return ApiResponder::bad_request(
+                ErrorMessage::InvitationInvalid.to_string(),
+                None::<()>,
+            );
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let inserted = sqlx::query(r"INSERT INTO users (username, name, role, password) VALUES (?, ?, ?, ?)")
+        .bind(&request.username)
+        .bind(&request.name)
+        .bind(&role)
+        .bind(hashed_password)
+        .execute(&mut tx)
+        .await;
+
+    let user_id = match inserted {
+        Ok(res) => res.last_insert_id() as i32,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let consumed = sqlx::query(r"UPDATE invitations SET used_at = ?, used_by = ? WHERE id = ?")
+        .bind(now)
+        .bind(user_id)
+        .bind(invitation_id)
+        .execute(&mut tx)
+        .await;
+
+    if let Err(e) = consumed {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => ApiResponder::created(
+            ErrorMessage::CreateDataSuccess.to_string(),
+            Some(UserResponse {
+                id: user_id,
+                username: request.username,
+                name: request.name,
+                role,
+                profile_picture: None,
+                is_active: true,
+            }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
@@ -0,0 +1,10 @@
+use actix_web::{HttpResponse, Responder, http::header};
+
+use crate::config::jwt::jwt_keys;
+
+// Public verification keys, served as a plain JWK set so standard JWT libraries can read it
+pub async fn get_jwks() -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(jwt_keys().jwks())
+}
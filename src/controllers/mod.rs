@@ -5,4 +5,11 @@ pub mod group_task;
 pub mod group;
 pub mod auth;
 pub mod session;
-pub mod course;
\ No newline at end of file
+pub mod course;
+pub mod password_reset;
+pub mod two_factor;
+pub mod personal_token;
+pub mod jwks;
+pub mod oidc;
+pub mod invitation;
+pub mod profile_picture;
\ No newline at end of file
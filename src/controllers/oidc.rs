@@ -0,0 +1,299 @@
+use actix_web::{
+    HttpRequest, HttpResponse, Responder,
+    cookie::{Cookie, SameSite, time},
+    http::header,
+    web,
+};
+use chrono::Duration;
+use sqlx::MySqlPool;
+
+use crate::{
+    config::oidc::{OidcSettings, oidc_settings},
+    controllers::auth::{token_response, two_factor_challenge},
+    middleware::auth::AuthUser,
+    models::{
+        message::ErrorMessage,
+        oidc::{OidcCallbackQuery, OidcLoginState},
+        session::ClientInfo,
+    },
+    utils::{
+        jwt::create_token,
+        oidc::{OidcIdentity, authorization_request, exchange_code},
+        responder::ApiResponder,
+        security::hash_token,
+    },
+};
+
+// Time the user has to finish logging in at the identity provider
+const STATE_TTL_MINUTES: i64 = 10;
+
+// Holds the state of the flow this browser started, a callback from any other flow is refused
+const STATE_COOKIE: &str = "oidc_state";
+
+// Redirect the browser to the identity provider
+pub async fn oidc_login(pool: web::Data<MySqlPool>) -> impl Responder {
+    let settings = match oidc_settings() {
+        Some(settings) => settings,
+        None => {
+            return ApiResponder::not_found(ErrorMessage::OidcNotConfigured.to_string(), None::<()>);
+        }
+    };
+
+    match start_authorization(&pool, settings, None).await {
+        Ok((url, state)) => HttpResponse::Found()
+            .insert_header((header::LOCATION, url))
+            .cookie(state_cookie(state))
+            .finish(),
+        Err(response) => response,
+    }
+}
+
+// Start linking an identity to the logged in user, the frontend opens the returned URL
+pub async fn link_oidc_identity(pool: web::Data<MySqlPool>, claims: AuthUser) -> impl Responder {
+    let settings = match oidc_settings() {
+        Some(settings) => settings,
+        None => {
+            return ApiResponder::not_found(ErrorMessage::OidcNotConfigured.to_string(), None::<()>);
+        }
+    };
+
+    match start_authorization(&pool, settings, Some(claims.user_id)).await {
+        Ok((url, state)) => ApiResponder::success_with_cookie(
+            ErrorMessage::Success.to_string(),
+            Some(serde_json::json!({ "authorization_url": url })),
+            vec![state_cookie(state)],
+        ),
+        Err(response) => response,
+    }
+}
+
+// Redirect target of the identity provider, logs in or finishes linking
+pub async fn oidc_callback(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    query: web::Query<OidcCallbackQuery>,
+) -> impl Responder {
+    let settings = match oidc_settings() {
+        Some(settings) => settings,
+        None => {
+            return ApiResponder::not_found(ErrorMessage::OidcNotConfigured.to_string(), None::<()>);
+        }
+    };
+
+    let query = query.into_inner();
+
+    if let Some(error) = query.error {
+        return ApiResponder::unauthorized(
+            ErrorMessage::OidcLoginFailed {
+                details: query.error_description.unwrap_or(error),
+            }
+            .to_string(),
+            None::<()>,
+        );
+    }
+
+    let (code, state) = match (query.code, query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => {
+            return ApiResponder::bad_request(ErrorMessage::OidcStateInvalid.to_string(), None::<()>);
+        }
+    };
+
+    // A state from a link sent by someone else never matches the cookie of this browser
+    let started_here = req
+        .cookie(STATE_COOKIE)
+        .is_some_and(|cookie| cookie.value() == state);
+
+    if !started_here {
+        return ApiResponder::bad_request(ErrorMessage::OidcStateInvalid.to_string(), None::<()>);
+    }
+
+    let login_state = match consume_state(&pool, &state).await {
+        Ok(Some(login_state)) => login_state,
+        Ok(None) => {
+            return ApiResponder::bad_request(ErrorMessage::OidcStateInvalid.to_string(), None::<()>);
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let identity =
+        match exchange_code(settings, &code, &login_state.pkce_verifier, &login_state.nonce).await {
+            Ok(identity) => identity,
+            Err(e) => {
+                return ApiResponder::unauthorized(
+                    ErrorMessage::OidcLoginFailed {
+                        details: e.to_string(),
+                    }
+                    .to_string(),
+                    None::<()>,
+                );
+            }
+        };
+
+    if let Some(user_id) = login_state.link_user_id {
+        return match link_identity(&pool, user_id, &identity).await {
+            Ok(true) => ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>),
+            Ok(false) => {
+                ApiResponder::conflict(ErrorMessage::IdentityAlreadyLinked.to_string(), None::<()>)
+            }
+            Err(e) => ApiResponder::<()>::handle_error(e),
+        };
+    }
+
+    let username = match find_linked_user(&pool, &identity).await {
+        Ok(Some(username)) => username,
+        Ok(None) => {
+            return ApiResponder::unauthorized(
+                ErrorMessage::OidcAccountNotLinked.to_string(),
+                None::<()>,
+            );
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    // The identity provider only counts as the first factor
+    if let Some(challenge) = two_factor_challenge(&pool, &username).await {
+        return challenge;
+    }
+
+    let client = ClientInfo::from_request(&req);
+
+    match create_token(&pool, &username, &client).await {
+        Ok(token) => token_response(token),
+        Err(e) => ApiResponder::unauthorized(
+            ErrorMessage::TokenGenerateFailed {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        ),
+    }
+}
+
+async fn start_authorization(
+    pool: &web::Data<MySqlPool>,
+    settings: &OidcSettings,
+    link_user_id: Option<i32>,
+) -> Result<(String, String), HttpResponse> {
+    let request = authorization_request(settings).await.map_err(|e| {
+        ApiResponder::error(
+            ErrorMessage::OidcLoginFailed {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        )
+    })?;
+
+    let now = chrono::Utc::now();
+
+    let cleanup = sqlx::query(r"DELETE FROM oidc_login_states WHERE expires_at < ?")
+        .bind(now.naive_utc())
+        .execute(pool.get_ref())
+        .await;
+
+    if let Err(e) = cleanup {
+        return Err(ApiResponder::<()>::handle_error(e));
+    }
+
+    let query = r"INSERT INTO oidc_login_states (state_hash, pkce_verifier, nonce, link_user_id, expires_at)
+                  VALUES (?, ?, ?, ?, ?)";
+
+    let result = sqlx::query(query)
+        .bind(hash_token(&request.state))
+        .bind(&request.pkce_verifier)
+        .bind(&request.nonce)
+        .bind(link_user_id)
+        .bind((now + Duration::minutes(STATE_TTL_MINUTES)).naive_utc())
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(_) => Ok((request.url, request.state)),
+        Err(e) => Err(ApiResponder::<()>::handle_error(e)),
+    }
+}
+
+// Lax so the browser still sends it on the top-level redirect back from the identity provider
+fn state_cookie(state: String) -> Cookie<'static> {
+    Cookie::build(STATE_COOKIE, state)
+        .http_only(true)
+        .secure(false)
+        .same_site(SameSite::Lax)
+        .path("/auth/oidc")
+        .max_age(time::Duration::minutes(STATE_TTL_MINUTES))
+        .finish()
+}
+
+// Single use, the row is deleted in the same transaction that reads it
+async fn consume_state(
+    pool: &web::Data<MySqlPool>,
+    state: &str,
+) -> Result<Option<OidcLoginState>, sqlx::Error> {
+    let state_hash = hash_token(state);
+    let mut tx = pool.begin().await?;
+
+    let login_state = sqlx::query_as::<_, OidcLoginState>(
+        r"SELECT pkce_verifier, nonce, link_user_id FROM oidc_login_states
+          WHERE state_hash = ? AND expires_at > ?
+          FOR UPDATE",
+    )
+    .bind(&state_hash)
+    .bind(chrono::Utc::now().naive_utc())
+    .fetch_optional(&mut tx)
+    .await?;
+
+    sqlx::query(r"DELETE FROM oidc_login_states WHERE state_hash = ?")
+        .bind(&state_hash)
+        .execute(&mut tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(login_state)
+}
+
+// Username of the user owning this identity. Identities are only linked by a logged in user,
+// claims like preferred_username are mutable and not unique so they never pick an account.
+async fn find_linked_user(
+    pool: &web::Data<MySqlPool>,
+    identity: &OidcIdentity,
+) -> Result<Option<String>, sqlx::Error> {
+    let query = r"SELECT u.username FROM user_identities i
+                  JOIN users u ON u.id = i.user_id
+                  WHERE i.issuer = ? AND i.subject = ? AND u.is_active = TRUE";
+
+    sqlx::query_scalar::<_, String>(query)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .fetch_optional(pool.get_ref())
+        .await
+}
+
+// False when the identity already belongs to another user
+async fn link_identity(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+    identity: &OidcIdentity,
+) -> Result<bool, sqlx::Error> {
+    let query = r"INSERT INTO user_identities (user_id, issuer, subject, email) VALUES (?, ?, ?, ?)
+                  ON DUPLICATE KEY UPDATE id = id";
+
+    sqlx::query(query)
+        .bind(user_id)
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(&identity.email)
+        .execute(pool.get_ref())
+        .await?;
+
+    let owner = sqlx::query_scalar::<_, i32>(
+        r"SELECT user_id FROM user_identities WHERE issuer = ? AND subject = ?",
+    )
+    .bind(&identity.issuer)
+    .bind(&identity.subject)
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(owner == user_id)
+}
@@ -0,0 +1,203 @@
+use actix_web::{Responder, web};
+use chrono::Duration;
+use nanoid::nanoid;
+use sqlx::MySqlPool;
+
+use crate::{
+    models::{
+        message::ErrorMessage,
+        password_reset::{ForgotPasswordRequest, PasswordResetNotice, ResetPasswordRequest},
+    },
+    utils::{
+        notifier::Notifier,
+        password_policy::validate_password,
+        responder::ApiResponder,
+        security::{hash_password, hash_token},
+        token_version::forget_token_version,
+    },
+};
+
+const RESET_TOKEN_LIFETIME_MINUTES: i64 = 30;
+
+// Issue a single-use reset token, the response never reveals whether the username exists
+pub async fn forgot_password(
+    pool: web::Data<MySqlPool>,
+    notifier: Option<web::Data<dyn Notifier>>,
+    data: web::Json<ForgotPasswordRequest>,
+) -> impl Responder {
+    let Some(notifier) = notifier else {
+        return ApiResponder::service_unavailable(
+            ErrorMessage::PasswordResetNotConfigured.to_string(),
+            None::<()>,
+        );
+    };
+
+    let user = sqlx::query_as::<_, (i32, String)>(r"SELECT id, username FROM users WHERE username = ? AND is_active = TRUE")
+        .bind(&data.username)
+        .fetch_optional(pool.get_ref())
+        .await;
+
+    let (user_id, username) = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return ApiResponder::success(
+                ErrorMessage::PasswordResetRequested.to_string(),
+                None::<()>,
+            );
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let token = nanoid!(32);
+    let now = chrono::Utc::now().naive_utc();
+    let expires_at = now + Duration::minutes(RESET_TOKEN_LIFETIME_MINUTES);
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    // Only the most recent token stays usable
+    let superseded = sqlx::query(
+        r"UPDATE password_reset_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL",
+    )
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut tx)
+    .await;
+
+    if let Err(e) = superseded {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let inserted = sqlx::query(
+        r"INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES (?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await;
+
+    if let Err(e) = inserted {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    if let Err(e) = tx.commit().await {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let notice = PasswordResetNotice {
+        user_id,
+        username,
+        token,
+        expires_at,
+    };
+
+    if let Err(e) = notifier.send_password_reset(&notice) {
+        tracing::error!(user_id, "Failed to deliver password reset token: {}", e);
+    }
+
+    ApiResponder::success(ErrorMessage::PasswordResetRequested.to_string(), None::<()>)
+}
+
+// Consume a reset token, set the new password and log out every device
+pub async fn reset_password(
+    pool: web::Data<MySqlPool>,
+    data: web::Json<ResetPasswordRequest>,
+) -> impl Responder {
+    let now = chrono::Utc::now().naive_utc();
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let query = r"SELECT t.user_id, u.username FROM password_reset_tokens t
+                  JOIN users u ON u.id = t.user_id
+                  WHERE t.token_hash = ? AND t.used_at IS NULL AND t.expires_at > ?
+                    AND u.is_active = TRUE
+                  FOR UPDATE";
+
+    let (user_id, username) = match sqlx::query_as::<_, (i32, String)>(query)
+        .bind(hash_token(&data.token))
+        .bind(now)
+        .fetch_optional(&mut tx)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return ApiResponder::bad_request(
+                ErrorMessage::PasswordResetTokenInvalid.to_string(),
+                None::<()>,
+            );
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let errors = validate_password("new_password", &data.new_password, &username);
+
+    if !errors.is_empty() {
+        return ApiResponder::unprocessable_entity(
+            ErrorMessage::PasswordPolicyViolation.to_string(),
+            Some(errors),
+        );
+    }
+
+    let new_password = match hash_password(&data.new_password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::PasswordHashFailed {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let consumed = sqlx::query(
+        r"UPDATE password_reset_tokens SET used_at = ? WHERE user_id = ? AND used_at IS NULL",
+    )
+    .bind(now)
+    .bind(user_id)
+    .execute(&mut tx)
+    .await;
+
+    if let Err(e) = consumed {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let updated =
+        sqlx::query(
+            r"UPDATE users SET password = ?, must_change_password = FALSE,
+              token_version = token_version + 1 WHERE id = ?",
+        )
+            .bind(new_password)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = updated {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let revoked =
+        sqlx::query(r"UPDATE sessions SET is_revoke = TRUE WHERE user_id = ? AND is_revoke = FALSE")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = revoked {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            forget_token_version(user_id);
+            ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>)
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
@@ -0,0 +1,210 @@
+use actix_web::{Responder, web};
+use chrono::Duration;
+use nanoid::nanoid;
+use sqlx::MySqlPool;
+
+use crate::{
+    config::{jwt::token_settings, totp::is_totp_required},
+    middleware::auth::AuthUser,
+    models::{
+        auth::Claims,
+        message::ErrorMessage,
+        permission::Permission,
+        personal_token::{
+            CreatePersonalTokenRequest, CreatedPersonalTokenResponse, PersonalTokenOwner,
+            PersonalTokenResponse, SCOPE_READ, SCOPE_WRITE,
+        },
+    },
+    utils::{jwt::parse_role, responder::ApiResponder, security::hash_token},
+};
+
+// Tells personal access tokens apart from JWTs in the Authorization header
+pub const PERSONAL_TOKEN_PREFIX: &str = "pat_";
+
+// Keeps expires_at well inside the DATETIME range
+const MAX_EXPIRES_IN_DAYS: i64 = 3650;
+
+// List the active personal access tokens of the logged in user
+pub async fn get_my_tokens(pool: web::Data<MySqlPool>, claims: AuthUser) -> impl Responder {
+    let query = r"SELECT id, name, scopes, expires_at, last_used_at, created_at
+                  FROM personal_access_tokens
+                  WHERE user_id = ? AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > ?)
+                  ORDER BY created_at DESC";
+
+    let result = sqlx::query_as::<_, PersonalTokenResponse>(query)
+        .bind(claims.user_id)
+        .bind(chrono::Utc::now().naive_utc())
+        .fetch_all(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(tokens) => ApiResponder::success(ErrorMessage::Success.to_string(), Some(tokens)),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Create a token, the plain value is only returned in this response
+pub async fn create_my_token(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    data: web::Json<CreatePersonalTokenRequest>,
+) -> impl Responder {
+    let request = data.into_inner();
+
+    if request.name.trim().is_empty() {
+        return ApiResponder::bad_request(ErrorMessage::CantBeNull.to_string(), None::<()>);
+    }
+
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in request.scopes {
+        let is_valid = match Permission::from_name(&scope) {
+            Some(permission) => claims.role.has_permission(permission),
+            None => scope == SCOPE_READ || scope == SCOPE_WRITE,
+        };
+
+        if !is_valid {
+            return ApiResponder::bad_request(
+                ErrorMessage::PersonalTokenScopeInvalid { details: scope }.to_string(),
+                None::<()>,
+            );
+        }
+
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    let expires_at = match request.expires_in_days {
+        Some(days) if (1..=MAX_EXPIRES_IN_DAYS).contains(&days) => {
+            Some((chrono::Utc::now() + Duration::days(days)).naive_utc())
+        }
+        Some(_) => {
+            return ApiResponder::bad_request(
+                ErrorMessage::Error {
+                    details: format!("expires_in_days must be between 1 and {}", MAX_EXPIRES_IN_DAYS),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+        None => None,
+    };
+
+    let token = format!("{}{}", PERSONAL_TOKEN_PREFIX, nanoid!(40));
+
+    let query = r"INSERT INTO personal_access_tokens (user_id, name, token_hash, scopes, expires_at)
+                  VALUES (?, ?, ?, ?, ?)";
+
+    let result = sqlx::query(query)
+        .bind(claims.user_id)
+        .bind(request.name.trim())
+        .bind(hash_token(&token))
+        .bind(scopes.join(","))
+        .bind(expires_at)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(res) => ApiResponder::created(
+            ErrorMessage::CreateDataSuccess.to_string(),
+            Some(CreatedPersonalTokenResponse {
+                id: res.last_insert_id() as i32,
+                name: request.name.trim().to_string(),
+                token,
+                scopes,
+                expires_at,
+            }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Revoke one of your own tokens
+pub async fn revoke_my_token(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    id: web::Path<i32>,
+) -> impl Responder {
+    let result = sqlx::query(
+        r"UPDATE personal_access_tokens SET revoked_at = ?
+          WHERE id = ? AND user_id = ? AND revoked_at IS NULL",
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(*id)
+    .bind(claims.user_id)
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(res) => {
+            if res.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
+            }
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Claims of the token owner, None when the token is unknown, expired or revoked
+pub async fn resolve_personal_token(
+    pool: &web::Data<MySqlPool>,
+    token: &str,
+) -> Result<Option<Claims>, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+
+    let query = r"SELECT t.id, t.user_id, t.scopes, t.expires_at,
+                         u.username, u.role, u.must_change_password, u.totp_enabled,
+                         u.token_version
+                  FROM personal_access_tokens t
+                  JOIN users u ON u.id = t.user_id
+                  WHERE t.token_hash = ? AND t.revoked_at IS NULL AND u.is_active = TRUE
+                    AND (t.expires_at IS NULL OR t.expires_at > ?)";
+
+    let owner = sqlx::query_as::<_, PersonalTokenOwner>(query)
+        .bind(hash_token(token))
+        .bind(now)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let owner = match owner {
+        Some(owner) => owner,
+        None => return Ok(None),
+    };
+
+    sqlx::query(r"UPDATE personal_access_tokens SET last_used_at = ? WHERE id = ?")
+        .bind(now)
+        .bind(owner.id)
+        .execute(pool.get_ref())
+        .await?;
+
+    let role = parse_role(&owner.role);
+    let settings = token_settings();
+    let token_id = format!("{}{}", PERSONAL_TOKEN_PREFIX, owner.id);
+
+    Ok(Some(Claims {
+        sub: owner.username,
+        user_id: owner.user_id,
+        must_enroll_totp: is_totp_required(&role) && !owner.totp_enabled,
+        role,
+        sid: token_id.clone(),
+        must_change_password: owner.must_change_password,
+        scopes: Some(
+            owner
+                .scopes
+                .split(',')
+                .filter(|scope| !scope.is_empty())
+                .map(|scope| scope.to_string())
+                .collect(),
+        ),
+        ver: owner.token_version,
+        iss: settings.issuer.clone(),
+        aud: settings.audience.clone(),
+        iat: now.and_utc().timestamp() as usize,
+        jti: token_id,
+        exp: owner
+            .expires_at
+            .map_or(usize::MAX, |expires_at| expires_at.and_utc().timestamp() as usize),
+    }))
+}
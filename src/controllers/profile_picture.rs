@@ -0,0 +1,257 @@
+use actix_multipart::Multipart;
+use actix_web::{
+    HttpResponse, Responder,
+    http::header::{CacheControl, CacheDirective},
+    web,
+};
+use futures_util::TryStreamExt;
+use nanoid::nanoid;
+use sqlx::MySqlPool;
+
+use crate::{
+    config::profile_picture::profile_picture_settings,
+    middleware::auth::AuthUser,
+    models::{
+        message::ErrorMessage,
+        permission::Permission,
+        profile_picture::{ProfilePictureResponse, ProfilePictureSize},
+    },
+    utils::{
+        profile_picture::{
+            PICTURE_SIZES, ProfilePictureError, picture_id_from_url, picture_key, picture_url,
+            resize_picture,
+        },
+        responder::ApiResponder,
+        storage::Storage,
+    },
+};
+
+// Stored pictures never change, a new upload gets a new picture id
+const CACHE_MAX_AGE_SECS: u32 = 365 * 24 * 60 * 60;
+
+// Upload a profile picture as the multipart field "file", stored in every size as PNG
+pub async fn upload_profile_picture(
+    pool: web::Data<MySqlPool>,
+    storage: web::Data<dyn Storage>,
+    claims: AuthUser,
+    path: web::Path<String>,
+    mut payload: Multipart,
+) -> impl Responder {
+    let username = path.into_inner();
+
+    let user = sqlx::query_as::<_, (i32, Option<String>)>(
+        r"SELECT id, profile_picture FROM users WHERE username = ? AND is_active = TRUE",
+    )
+    .bind(&username)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let (user_id, old_picture) = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if !claims.has_permission(Permission::UserUpdate) && claims.user_id != user_id {
+        return ApiResponder::forbidden(
+            ErrorMessage::InsufficientPermissions.to_string(),
+            None::<()>,
+        );
+    }
+
+    let max_bytes = profile_picture_settings().max_bytes;
+    let mut upload: Option<Vec<u8>> = None;
+
+    loop {
+        let mut field = match payload.try_next().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                return ApiResponder::bad_request(
+                    ErrorMessage::ProfilePictureInvalid {
+                        details: e.to_string(),
+                    }
+                    .to_string(),
+                    None::<()>,
+                );
+            }
+        };
+
+        if field.name() != "file" || upload.is_some() {
+            continue;
+        }
+
+        let mut bytes = Vec::new();
+
+        loop {
+            match field.try_next().await {
+                Ok(Some(chunk)) if bytes.len() + chunk.len() > max_bytes => {
+                    return ApiResponder::payload_too_large(
+                        ErrorMessage::ProfilePictureTooLarge { max_bytes }.to_string(),
+                        None::<()>,
+                    );
+                }
+                Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+                Ok(None) => break,
+                Err(e) => {
+                    return ApiResponder::bad_request(
+                        ErrorMessage::ProfilePictureInvalid {
+                            details: e.to_string(),
+                        }
+                        .to_string(),
+                        None::<()>,
+                    );
+                }
+            }
+        }
+
+        upload = Some(bytes);
+    }
+
+    let bytes = match upload {
+        Some(bytes) if !bytes.is_empty() => bytes,
+        _ => {
+            return ApiResponder::bad_request(
+                ErrorMessage::ProfilePictureMissing.to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    // Decoding and resizing are CPU heavy, keep them off the async workers
+    let resized = match web::block(move || resize_picture(&bytes)).await {
+        Ok(Ok(resized)) => resized,
+        Ok(Err(ProfilePictureError::Unsupported)) => {
+            return ApiResponder::unsupported_media_type(
+                ErrorMessage::ProfilePictureUnsupported.to_string(),
+                None::<()>,
+            );
+        }
+        Ok(Err(ProfilePictureError::Invalid(details))) => {
+            return ApiResponder::unprocessable_entity(
+                ErrorMessage::ProfilePictureInvalid { details }.to_string(),
+                None::<()>,
+            );
+        }
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::Error {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let picture_id = nanoid!(16);
+    let sizes: Vec<ProfilePictureSize> = resized
+        .iter()
+        .map(|(size, _)| ProfilePictureSize {
+            size: *size,
+            url: picture_url(&picture_key(user_id, &picture_id, *size)),
+        })
+        .collect();
+
+    let writer = storage.clone().into_inner();
+    let stored_id = picture_id.clone();
+    let stored = web::block(move || {
+        resized
+            .iter()
+            .try_for_each(|(size, png)| writer.put(&picture_key(user_id, &stored_id, *size), png))
+    })
+    .await;
+
+    let failure = match stored {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(e) => Some(e.to_string()),
+    };
+
+    if let Some(details) = failure {
+        return ApiResponder::error(ErrorMessage::Error { details }.to_string(), None::<()>);
+    }
+
+    let profile_picture = sizes[0].url.clone();
+
+    let updated = sqlx::query(r"UPDATE users SET profile_picture = ? WHERE id = ?")
+        .bind(&profile_picture)
+        .bind(user_id)
+        .execute(pool.get_ref())
+        .await;
+
+    if let Err(e) = updated {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    // The previous upload is no longer referenced, a failed cleanup only leaves orphan files
+    if let Some(old_id) = old_picture.and_then(|url| picture_id_from_url(user_id, &url)) {
+        let remover = storage.into_inner();
+        let removed = web::block(move || {
+            PICTURE_SIZES
+                .iter()
+                .try_for_each(|size| remover.delete(&picture_key(user_id, &old_id, *size)))
+        })
+        .await;
+
+        if let Ok(Err(e)) = removed {
+            tracing::warn!(user_id, "Failed to delete old profile picture: {}", e);
+        }
+    }
+
+    ApiResponder::success(
+        ErrorMessage::UpdateDataSuccess.to_string(),
+        Some(ProfilePictureResponse {
+            profile_picture,
+            sizes,
+        }),
+    )
+}
+
+// Serve a stored picture, public so it works in <img> tags
+pub async fn get_profile_picture(
+    storage: web::Data<dyn Storage>,
+    path: web::Path<(i32, String)>,
+) -> impl Responder {
+    let (user_id, file) = path.into_inner();
+
+    let is_picture_file = file.strip_suffix(".png").is_some_and(|name| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    });
+
+    if !is_picture_file {
+        return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>);
+    }
+
+    let key = format!("profile-pictures/{}/{}", user_id, file);
+    let reader = storage.into_inner();
+
+    match web::block(move || reader.get(&key)).await {
+        Ok(Ok(Some(bytes))) => HttpResponse::Ok()
+            .content_type("image/png")
+            .insert_header(CacheControl(vec![
+                CacheDirective::Public,
+                CacheDirective::MaxAge(CACHE_MAX_AGE_SECS),
+                CacheDirective::Extension("immutable".to_string(), None),
+            ]))
+            .body(bytes),
+        Ok(Ok(None)) => ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Ok(Err(e)) => ApiResponder::error(
+            ErrorMessage::Error {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        ),
+        Err(e) => ApiResponder::error(
+            ErrorMessage::Error {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        ),
+    }
+}
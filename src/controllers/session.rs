@@ -1,181 +1,406 @@
-use std::env;
+use std::sync::LazyLock;
 
-use actix_web::{HttpRequest, HttpResponse, cookie::Cookie, web};
-use chrono::{DateTime, Duration, Utc};
-use sqlx::{MySqlPool, Row};
+use actix_web::{HttpRequest, HttpResponse, Responder, cookie::Cookie, web};
+use chrono::Duration;
+use sqlx::MySqlPool;
 use uuid::Uuid;
 
 use crate::{
-    models::message::ErrorMessage,
+    config::jwt::token_settings,
+    middleware::auth::AuthUser,
+    models::{
+        message::ErrorMessage,
+        session::{
+            ClientInfo, CreateSessionRequest, RevokeSessionRequest, SessionResponse, SessionRow,
+        },
+        permission::Permission,
+    },
     utils::{
-        jwt::{create_token},
+        cache::TtlCache,
+        jwt::{create_access_token, request_claims},
         responder::ApiResponder,
+        token_version::CACHE_TTL,
     },
 };
 
+// Revoked state by session_id, other instances see a revocation after at most the cache TTL
+static REVOKED_SESSIONS: LazyLock<TtlCache<String, bool>> =
+    LazyLock::new(|| TtlCache::new(*CACHE_TTL));
+
 pub async fn renew_access_token(
     pool: web::Data<MySqlPool>,
     req: HttpRequest,
 ) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    let cookies = req.cookies()?;
-
-    tracing::info!("{:?}", cookies);
-
-    if let Some(refresh_cookie) = cookies.iter().find(|c| c.name() == "refresh_token") {
-        let refresh_token = refresh_cookie.value();
-
-        if let Some(user_id) = get_user_id_from_refresh_token(&pool, &refresh_token).await {
-            let is_valid = validation_refresh_token(&pool, user_id, refresh_token).await;
-
-            if is_valid {
-                {
-                    let new_token =
-                        create_token(&pool, &env::var("SECRET_KEY")?, &user_id.to_string()).await?;
-
-                    let access_cookie =
-                        Cookie::build("access_token", new_token.access_token.clone())
-                            .http_only(true)
-                            .secure(false)
-                            .path("/")
-                            .finish();
-
-                    let refresh_cookie =
-                        Cookie::build("refresh_token", new_token.refresh_token.clone())
-                            .http_only(true)
-                            .secure(false)
-                            .path("/")
-                            .finish();
-
-                    let cookies = vec![access_cookie, refresh_cookie];
-
-                    return Ok(ApiResponder::success_with_cookie(
-                        ErrorMessage::Success.to_string(),
-                        Some(serde_json::json!({ "token": new_token.access_token })),
-                        cookies,
-                    ));
-                };
-            } else {
-                return Ok(ApiResponder::unauthorized(
-                    ErrorMessage::RefreshTokenInvalid.to_string(),
-                    None::<()>,
-                ));
-            }
-        } else {
+    let refresh_cookie = match req.cookie("refresh_token") {
+        Some(cookie) => cookie,
+        None => {
+            return Ok(ApiResponder::bad_request(
+                ErrorMessage::RefreshTokenInvalid.to_string(),
+                None::<()>,
+            ));
+        }
+    };
+
+    let session = match validation_refresh_token(&pool, refresh_cookie.value()).await? {
+        Some(session) => session,
+        None => {
+            detect_refresh_token_reuse(&pool, refresh_cookie.value()).await?;
+
             return Ok(ApiResponder::unauthorized(
                 ErrorMessage::RefreshTokenInvalid.to_string(),
                 None::<()>,
             ));
-        };
-    }
+        }
+    };
+
+    let refresh_token = match rotate_refresh_token(&pool, &session).await? {
+        Some(token) => token,
+        None => {
+            return Ok(ApiResponder::unauthorized(
+                ErrorMessage::RefreshTokenInvalid.to_string(),
+                None::<()>,
+            ));
+        }
+    };
+
+    let access_token = create_access_token(&pool, session.user_id, &session.session_id).await?;
+
+    let access_cookie = Cookie::build("access_token", access_token.clone())
+        .http_only(true)
+        .secure(false)
+        .path("/")
+        .finish();
+
+    let refresh_cookie = Cookie::build("refresh_token", refresh_token)
+        .http_only(true)
+        .secure(false)
+        .path("/")
+        .finish();
 
-    Ok(ApiResponder::bad_request(
-        ErrorMessage::RefreshTokenInvalid.to_string(),
-        None::<()>,
+    let cookies = vec![access_cookie, refresh_cookie];
+
+    Ok(ApiResponder::success_with_cookie(
+        ErrorMessage::Success.to_string(),
+        Some(serde_json::json!({ "token": access_token })),
+        cookies,
     ))
 }
 
-pub async fn get_or_create_refresh_token(
-    pool: &web::Data<MySqlPool>,
-    user_id: i32,
-    session_id: String,
-) -> Result<String, sqlx::Error> {
-    if let Some(token) = check_refresh_token(&pool, user_id).await? {
-        Ok(token)
+// Get active sessions of the logged in user
+pub async fn get_my_sessions(pool: web::Data<MySqlPool>, claims: AuthUser) -> impl Responder {
+    match fetch_active_sessions(&pool, claims.user_id).await {
+        Ok(mut sessions) => {
+            for session in sessions.iter_mut() {
+                session.is_current = session.session_id == claims.sid;
+            }
+
+            ApiResponder::success(ErrorMessage::Success.to_string(), Some(sessions))
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Revoke one session, without session.manage only your own sessions
+pub async fn revoke_my_session(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    path: web::Path<String>,
+) -> impl Responder {
+    let session_id = path.into_inner();
+
+    let result = if claims.has_permission(Permission::SessionManage) {
+        sqlx::query(r"UPDATE sessions SET is_revoke = TRUE WHERE session_id = ? AND is_revoke = FALSE")
+            .bind(&session_id)
+            .execute(pool.get_ref())
+            .await
     } else {
-        create_refresh_token(&pool, user_id, session_id).await
+        sqlx::query(
+            r"UPDATE sessions SET is_revoke = TRUE
+              WHERE session_id = ? AND user_id = ? AND is_revoke = FALSE",
+        )
+        .bind(&session_id)
+        .bind(claims.user_id)
+        .execute(pool.get_ref())
+        .await
+    };
+
+    match result {
+        Ok(res) => {
+            if res.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                forget_session(&session_id);
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
+            }
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Get active sessions of any user, guarded by session.manage
+pub async fn get_user_sessions(
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<i32>,
+) -> impl Responder {
+    match fetch_active_sessions(&pool, *user_id).await {
+        Ok(sessions) => ApiResponder::success(ErrorMessage::Success.to_string(), Some(sessions)),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Revoke all sessions of any user, guarded by session.manage
+pub async fn revoke_all_user_sessions(
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<i32>,
+) -> impl Responder {
+    match revoke_user_sessions(&pool, *user_id).await {
+        Ok(revoked) => ApiResponder::success(
+            ErrorMessage::DeleteSuccess.to_string(),
+            Some(serde_json::json!({ "revoked_sessions": revoked })),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
+pub async fn fetch_active_sessions(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+) -> Result<Vec<SessionResponse>, sqlx::Error> {
+    let query = r"SELECT session_id, user_agent, ip_address, created_at, last_used_at, expires_at
+                  FROM sessions
+                  WHERE user_id = ? AND is_revoke = FALSE AND expires_at > ?
+                  ORDER BY COALESCE(last_used_at, created_at) DESC";
+
+    sqlx::query_as::<_, SessionResponse>(query)
+        .bind(user_id)
+        .bind(chrono::Utc::now().naive_utc())
+        .fetch_all(pool.get_ref())
+        .await
+}
+
+// Create a new session row for a login, every device gets its own refresh token
 pub async fn create_refresh_token(
     pool: &web::Data<MySqlPool>,
     user_id: i32,
     session_id: String,
+    client: &ClientInfo,
 ) -> Result<String, sqlx::Error> {
-    let query = r"INSERT INTO sessions (session_id, user_id, refresh_token, expires_at) 
-                  VALUES (?, ?, ?, ?)";
+    let expires_at = chrono::Utc::now() + Duration::seconds(token_settings().refresh_token_ttl_secs);
+
+    let session = CreateSessionRequest {
+        session_id,
+        user_id,
+        refresh_token: Uuid::new_v4().to_string(),
+        user_agent: client.user_agent.clone(),
+        ip_address: client.ip_address.clone(),
+        expires_at: expires_at.naive_utc(),
+    };
 
-    let refresh_token = Uuid::new_v4().to_string();
-    let created_at = chrono::Utc::now();
-    let expires_at = created_at + Duration::hours(720); // 30 days
+    let query = r"INSERT INTO sessions (session_id, user_id, refresh_token, user_agent, ip_address, expires_at)
+                  VALUES (?, ?, ?, ?, ?, ?)";
 
     sqlx::query(query)
-        .bind(&session_id)
-        .bind(user_id)
-        .bind(&refresh_token)
-        .bind(&expires_at)
+        .bind(&session.session_id)
+        .bind(session.user_id)
+        .bind(&session.refresh_token)
+        .bind(&session.user_agent)
+        .bind(&session.ip_address)
+        .bind(session.expires_at)
         .execute(pool.get_ref())
         .await?;
 
-    Ok(refresh_token)
+    Ok(session.refresh_token)
 }
 
-pub async fn check_refresh_token(
-    pool: &MySqlPool,
-    user_id: i32,
+// Find the session owning this refresh token, None when unknown, expired or revoked
+pub async fn validation_refresh_token(
+    pool: &web::Data<MySqlPool>,
+    refresh_token: &str,
+) -> Result<Option<SessionRow>, sqlx::Error> {
+    let query = r"SELECT session_id, user_id, refresh_token, expires_at FROM sessions
+                  WHERE refresh_token = ? AND expires_at > ? AND is_revoke = FALSE";
+
+    sqlx::query_as::<_, SessionRow>(query)
+        .bind(refresh_token)
+        .bind(chrono::Utc::now().naive_utc())
+        .fetch_optional(pool.get_ref())
+        .await
+}
+
+// Swap the session refresh token for a new one and keep the old one to detect reuse.
+// Returns None when another request already rotated the same token.
+pub async fn rotate_refresh_token(
+    pool: &web::Data<MySqlPool>,
+    session: &SessionRow,
 ) -> Result<Option<String>, sqlx::Error> {
-    let query = r"SELECT expires_at, refresh_token FROM sessions WHERE user_id = ?";
+    let new_refresh_token = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now().naive_utc();
 
-    let result = sqlx::query(query)
-        .bind(user_id)
-        .fetch_optional(pool)
-        .await?;
+    let mut tx = pool.begin().await?;
 
-    if let Some(row) = result {
-        let expires_at: DateTime<Utc> = row.get("expires_at");
-        let token: String = row.get("refresh_token");
+    let result = sqlx::query(
+        r"UPDATE sessions SET refresh_token = ?, last_used_at = ?
+          WHERE session_id = ? AND refresh_token = ? AND is_revoke = FALSE",
+    )
+    .bind(&new_refresh_token)
+    .bind(now)
+    .bind(&session.session_id)
+    .bind(&session.refresh_token)
+    .execute(&mut tx)
+    .await?;
 
-        if expires_at < chrono::Utc::now() {
-            Ok(None)
-        } else {
-            Ok(Some(token))
-        }
-    } else {
-        Ok(None)
+    if result.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(None);
     }
+
+    sqlx::query(
+        r"INSERT INTO rotated_refresh_tokens (refresh_token, session_id, rotated_at)
+          VALUES (?, ?, ?)",
+    )
+    .bind(&session.refresh_token)
+    .bind(&session.session_id)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(new_refresh_token))
 }
 
-pub async fn validation_refresh_token(
+// A rotated refresh token presented again means it was stolen, revoke the whole session
+pub async fn detect_refresh_token_reuse(
     pool: &web::Data<MySqlPool>,
-    user_id: i32,
     refresh_token: &str,
-) -> bool {
-    let query = r"SELECT refresh_token from sessions WHERE user_id = ?";
+) -> Result<(), sqlx::Error> {
+    let query = r"SELECT r.session_id, s.user_id FROM rotated_refresh_tokens r
+                  JOIN sessions s ON s.session_id = r.session_id
+                  WHERE r.refresh_token = ?";
 
-    let result = sqlx::query(query)
-        .bind(&user_id)
-        .fetch_one(pool.get_ref())
-        .await;
+    let reused = sqlx::query_as::<_, (String, i32)>(query)
+        .bind(refresh_token)
+        .fetch_optional(pool.get_ref())
+        .await?;
 
-    match result {
-        Ok(row) => {
-            let refresh_token_db: String = row.get("refresh_token");
+    if let Some((session_id, user_id)) = reused {
+        tracing::warn!(
+            session_id = %session_id,
+            user_id,
+            "Refresh token reuse detected, revoking session"
+        );
 
-            if refresh_token_db == refresh_token {
-                true
-            } else {
-                false
-            }
-        }
-        Err(_) => false,
+        revoke_session(
+            pool,
+            &RevokeSessionRequest {
+                session_id,
+                is_revoke: true,
+            },
+        )
+        .await?;
     }
+
+    Ok(())
 }
 
-pub async fn get_user_id_from_refresh_token(
+pub async fn revoke_session(
     pool: &web::Data<MySqlPool>,
-    refresh_token: &str,
-) -> Option<i32> {
-    let query = r"Select user_id from sessions Where refresh_token = ?";
+    request: &RevokeSessionRequest,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(r"UPDATE sessions SET is_revoke = ? WHERE session_id = ?")
+        .bind(request.is_revoke)
+        .bind(&request.session_id)
+        .execute(pool.get_ref())
+        .await?;
 
-    let result = sqlx::query(query)
-        .bind(&refresh_token)
-        .fetch_one(pool.get_ref())
-        .await;
+    forget_session(&request.session_id);
 
-    match result {
-        Ok(row) => {
-            let user_id: i32 = row.get("user_id");
-            Some(user_id)
+    Ok(result.rows_affected())
+}
+
+// Access tokens of a revoked or unknown session are rejected like its refresh token
+pub async fn is_session_revoked(
+    pool: &web::Data<MySqlPool>,
+    session_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let session_id = session_id.to_string();
+
+    if let Some(revoked) = REVOKED_SESSIONS.get(&session_id) {
+        return Ok(revoked);
+    }
+
+    let revoked =
+        sqlx::query_scalar::<_, bool>(r"SELECT is_revoke FROM sessions WHERE session_id = ?")
+            .bind(&session_id)
+            .fetch_optional(pool.get_ref())
+            .await?
+            .unwrap_or(true);
+
+    REVOKED_SESSIONS.insert(session_id, revoked);
+
+    Ok(revoked)
+}
+
+// Call after revoking a single session so this instance rejects its access token at once
+fn forget_session(session_id: &str) {
+    REVOKED_SESSIONS.remove(&session_id.to_string());
+}
+
+// Revoke every session of a user, used by "log out everywhere"
+pub async fn revoke_user_sessions(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+) -> Result<u64, sqlx::Error> {
+    let result =
+        sqlx::query(r"UPDATE sessions SET is_revoke = TRUE WHERE user_id = ? AND is_revoke = FALSE")
+            .bind(user_id)
+            .execute(pool.get_ref())
+            .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Revoke every session of a user except the one making the request
+pub async fn revoke_other_sessions(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+    session_id: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r"UPDATE sessions SET is_revoke = TRUE
+          WHERE user_id = ? AND session_id <> ? AND is_revoke = FALSE",
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Session of the caller, taken from the refresh token cookie or the access token
+pub async fn current_session(
+    pool: &web::Data<MySqlPool>,
+    req: &HttpRequest,
+) -> Result<Option<(String, i32)>, sqlx::Error> {
+    if let Some(refresh_cookie) = req.cookie("refresh_token") {
+        // A revoked or expired refresh cookie no longer names a session
+        let query = r"SELECT session_id, user_id FROM sessions
+                      WHERE refresh_token = ? AND expires_at > ? AND is_revoke = FALSE";
+
+        let session = sqlx::query_as::<_, (String, i32)>(query)
+            .bind(refresh_cookie.value())
+            .bind(chrono::Utc::now().naive_utc())
+            .fetch_optional(pool.get_ref())
+            .await?;
+
+        if session.is_some() {
+            return Ok(session);
         }
-        Err(_) => None,
     }
+
+    Ok(request_claims(pool, req)
+        .await
+        .ok()
+        .map(|claims| (claims.sid, claims.user_id)))
 }
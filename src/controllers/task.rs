@@ -1,4 +1,4 @@
-use actix_web::{HttpRequest, Responder, web};
+use actix_web::{Responder, web};
 use sqlx::mysql::MySqlPool;
 
 use crate::{
@@ -9,9 +9,9 @@ use crate::{
             CreateTaskRequest, FinishedTaskRequest, GroupTaskStatusResponse, TaskResponse,
             TaskType, UpdateTaskRequest, UserTaskStatusResponse,
         },
-        users::{Role, UserResponse},
+        users::UserResponse,
     },
-    utils::{jwt::extract_claims, responder::ApiResponder},
+    utils::responder::ApiResponder,
 };
 
 // Get All task from database
@@ -48,125 +48,89 @@ pub async fn get_task(pool: web::Data<MySqlPool>, id: web::Path<i32>) -> impl Re
 // Create task to database
 pub async fn create_task(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     data: web::Json<CreateTaskRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
+    let task_type = match TaskType::try_from(data.task_type) {
+        Ok(t) => t,
+        Err(e) => {
+            return ApiResponder::bad_request(
+                ErrorMessage::TaskTypeError {
+                    details: e.to_owned(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
     };
 
-    if Role::has_permission(&claims.role) {
-        let task_type = match TaskType::try_from(data.task_type) {
-            Ok(t) => t,
-            Err(e) => {
-                return ApiResponder::bad_request(
-                    ErrorMessage::TaskTypeError {
-                        details: e.to_owned(),
-                    }
-                    .to_string(),
-                    None::<()>,
-                );
-            }
-        };
-
-        let task_query = r"INSERT INTO tasks (course, title, description, task_type, due_date) VALUES (?, ?, ?, ?, ?)";
-
-        let result = sqlx::query(task_query)
-            .bind(&data.course)
-            .bind(&data.title)
-            .bind(&data.description)
-            .bind(task_type as i32)
-            .bind(&data.due_date)
-            .execute(pool.get_ref())
-            .await;
-
-        match result {
-            Ok(res) => {
-                let inserted_id = res.last_insert_id();
-                ApiResponder::success(
-                    ErrorMessage::Success.to_string(),
-                    Some(TaskResponse {
-                        task_id: inserted_id as i32,
-                        course: data.course.clone(),
-                        title: data.title.clone(),
-                        description: data.description.clone(),
-                        task_type: task_type as i32,
-                        due_date: data.due_date,
-                    }),
-                )
-            }
-            Err(e) => ApiResponder::<()>::handle_error(e),
+    let task_query = r"INSERT INTO tasks (course, title, description, task_type, due_date) VALUES (?, ?, ?, ?, ?)";
+
+    let result = sqlx::query(task_query)
+        .bind(&data.course)
+        .bind(&data.title)
+        .bind(&data.description)
+        .bind(task_type as i32)
+        .bind(data.due_date)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(res) => {
+            let inserted_id = res.last_insert_id();
+            ApiResponder::success(
+                ErrorMessage::Success.to_string(),
+                Some(TaskResponse {
+                    task_id: inserted_id as i32,
+                    course: data.course.clone(),
+                    title: data.title.clone(),
+                    description: data.description.clone(),
+                    task_type: task_type as i32,
+                    due_date: data.due_date,
+                }),
+            )
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
 // Delete task from database with id
 pub async fn delete_task(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     id: web::Path<i32>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
-
-    if Role::has_permission(&claims.role) {
-        let query = "Delete from tasks where id = ?";
+    let query = "Delete from tasks where id = ?";
 
-        let result = sqlx::query(query).bind(*id).execute(pool.get_ref()).await;
+    let result = sqlx::query(query).bind(*id).execute(pool.get_ref()).await;
 
-        match result {
-            Ok(e) => {
-                if e.rows_affected() == 0 {
-                    ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
-                } else {
-                    ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
-                }
+    match result {
+        Ok(e) => {
+            if e.rows_affected() == 0 {
+                ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
+            } else {
+                ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
             }
-            Err(e) => ApiResponder::<()>::handle_error(e),
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
 // Update task detail
 pub async fn update_task(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
     data_req: web::Json<UpdateTaskRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
+    let query = r"UPDATE tasks SET title = ?, description = ? WHERE id = ?";
 
-    if Role::has_permission(&claims.role) {
-        let query = r"UPDATE tasks SET title = ?, description = ? WHERE id = ?";
-
-        let response = sqlx::query(query)
-            .bind(&data_req.title)
-            .bind(&data_req.description)
-            .bind(&data_req.task_id)
-            .execute(pool.get_ref())
-            .await;
+    let response = sqlx::query(query)
+        .bind(&data_req.title)
+        .bind(&data_req.description)
+        .bind(data_req.task_id)
+        .execute(pool.get_ref())
+        .await;
 
-        match response {
-            Ok(_) => ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>),
-            Err(e) => ApiResponder::<()>::handle_error(e)
-        }
-    } else {
-        ApiResponder::unauthorized(ErrorMessage::InsufficientPermissions.to_string(), None::<()>)
+    match response {
+        Ok(_) => ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>),
+        Err(e) => ApiResponder::<()>::handle_error(e)
     }
 }
 
@@ -178,8 +142,8 @@ pub async fn create_finished_task(
     let query = r"INSERT INTO finished_user_tasks (task_id, user_id) VALUES (?, ?) ";
 
     let result = sqlx::query(query)
-        .bind(&request.task_id)
-        .bind(&request.user_id)
+        .bind(request.task_id)
+        .bind(request.user_id)
         .execute(pool.get_ref())
         .await;
 
@@ -227,6 +191,7 @@ pub async fn get_task_status(
                             u.name,
                             u.role,
                             u.profile_picture,
+                            u.is_active,
                             fu.finished_at
                         FROM tasks t
                         JOIN finished_user_tasks fu ON fu.task_id = t.id
@@ -247,9 +212,10 @@ pub async fn get_task_status(
                             u.username,
                             u.name,
                             u.role,
-                            u.profile_picture
+                            u.profile_picture,
+                            u.is_active
                         FROM tasks t
-                        JOIN users u ON 1=1
+                        JOIN users u ON u.is_active = TRUE
                         WHERE t.id = ?
                         AND NOT EXISTS (
                             SELECT 1
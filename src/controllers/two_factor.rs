@@ -0,0 +1,360 @@
+use actix_web::{HttpRequest, Responder, web};
+use sqlx::MySqlPool;
+
+use crate::{
+    config::totp::is_totp_required,
+    controllers::auth::token_response,
+    middleware::auth::AuthUser,
+    models::{
+        message::ErrorMessage,
+        session::ClientInfo,
+        two_factor::{
+            RecoveryCodesResponse, TotpCodeRequest, TotpDisableRequest, TotpEnrollResponse,
+            TotpLoginRequest, TotpState,
+        },
+    },
+    utils::{
+        jwt::{
+            consume_challenge_token, create_token, decode_challenge_token,
+            is_access_token_revoked,
+        },
+        responder::ApiResponder,
+        security::{apply_deferred_rehash, verify_password},
+        throttle::{USERNAME_POLICY, clear_failed_logins, reserve_login_attempt},
+        totp::{
+            generate_recovery_codes, generate_secret, hash_recovery_code, otpauth_uri,
+            verify_code,
+        },
+    },
+};
+
+// Start enrolment, the secret is only active after a code is confirmed
+pub async fn enroll_totp(pool: web::Data<MySqlPool>, claims: AuthUser) -> impl Responder {
+    let state = match fetch_totp_state(&pool, claims.user_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if state.totp_enabled {
+        return ApiResponder::conflict(ErrorMessage::TotpAlreadyEnabled.to_string(), None::<()>);
+    }
+
+    let secret = generate_secret();
+
+    let otpauth_uri = match otpauth_uri(&secret, &state.username) {
+        Some(uri) => uri,
+        None => {
+            return ApiResponder::error(
+                ErrorMessage::Error {
+                    details: "Failed to build otpauth URI".to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let result = sqlx::query(r"UPDATE users SET totp_secret = ? WHERE id = ?")
+        .bind(&secret)
+        .bind(state.id)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(_) => ApiResponder::success(
+            ErrorMessage::Success.to_string(),
+            Some(TotpEnrollResponse {
+                secret,
+                otpauth_uri,
+            }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Confirm enrolment with a first code, returns the recovery codes once
+pub async fn confirm_totp(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    data: web::Json<TotpCodeRequest>,
+) -> impl Responder {
+    let state = match fetch_totp_state(&pool, claims.user_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if state.totp_enabled {
+        return ApiResponder::conflict(ErrorMessage::TotpAlreadyEnabled.to_string(), None::<()>);
+    }
+
+    let secret = match state.totp_secret {
+        Some(secret) => secret,
+        None => {
+            return ApiResponder::bad_request(ErrorMessage::TotpNotEnrolled.to_string(), None::<()>);
+        }
+    };
+
+    let step = match verify_code(&secret, &state.username, &data.code) {
+        Some(step) => step,
+        None => {
+            return ApiResponder::unauthorized(
+                ErrorMessage::TotpCodeInvalid.to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let recovery_codes = generate_recovery_codes();
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let enabled =
+        sqlx::query(r"UPDATE users SET totp_enabled = TRUE, totp_last_step = ? WHERE id = ?")
+            .bind(step as i64)
+            .bind(state.id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = enabled {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let cleared = sqlx::query(r"DELETE FROM user_recovery_codes WHERE user_id = ?")
+        .bind(state.id)
+        .execute(&mut tx)
+        .await;
+
+    if let Err(e) = cleared {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    for code in &recovery_codes {
+        let inserted =
+            sqlx::query(r"INSERT INTO user_recovery_codes (user_id, code_hash) VALUES (?, ?)")
+                .bind(state.id)
+                .bind(hash_recovery_code(code))
+                .execute(&mut tx)
+                .await;
+
+        if let Err(e) = inserted {
+            return ApiResponder::<()>::handle_error(e);
+        }
+    }
+
+    match tx.commit().await {
+        Ok(_) => ApiResponder::success(
+            ErrorMessage::UpdateDataSuccess.to_string(),
+            Some(RecoveryCodesResponse { recovery_codes }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Turn TOTP off, not allowed for roles where it is enforced
+pub async fn disable_totp(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    data: web::Json<TotpDisableRequest>,
+) -> impl Responder {
+    if is_totp_required(&claims.role) {
+        return ApiResponder::forbidden(ErrorMessage::TotpRequiredForRole.to_string(), None::<()>);
+    }
+
+    if !verify_password(&pool, &claims.sub, &data.password).await {
+        return ApiResponder::unauthorized(
+            ErrorMessage::CurrentPasswordInvalid.to_string(),
+            None::<()>,
+        );
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let disabled =
+        sqlx::query(
+            r"UPDATE users SET totp_enabled = FALSE, totp_secret = NULL, totp_last_step = NULL
+              WHERE id = ?",
+        )
+            .bind(claims.user_id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = disabled {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let cleared = sqlx::query(r"DELETE FROM user_recovery_codes WHERE user_id = ?")
+        .bind(claims.user_id)
+        .execute(&mut tx)
+        .await;
+
+    if let Err(e) = cleared {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Second login step, exchange the challenge token and a TOTP or recovery code for a Token
+pub async fn verify_totp_login(
+    pool: web::Data<MySqlPool>,
+    req: HttpRequest,
+    data: web::Json<TotpLoginRequest>,
+) -> impl Responder {
+    let challenge = match decode_challenge_token(&data.challenge_token) {
+        Ok(challenge) => challenge,
+        Err(e) => return ApiResponder::unauthorized(e, None::<()>),
+    };
+
+    match is_access_token_revoked(&pool, &challenge.jti).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return ApiResponder::unauthorized(ErrorMessage::TokenInvalid.to_string(), None::<()>);
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    }
+
+    // Counted as a failed attempt until the code is accepted
+    match reserve_login_attempt(&pool, &USERNAME_POLICY, &challenge.sub).await {
+        Ok(Some(retry_after_secs)) => {
+            return ApiResponder::too_many_requests(
+                ErrorMessage::LoginThrottled.to_string(),
+                None::<()>,
+                retry_after_secs,
+            );
+        }
+        Ok(None) => {}
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    }
+
+    let state = match fetch_totp_state(&pool, challenge.user_id).await {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            return ApiResponder::unauthorized(ErrorMessage::TokenInvalid.to_string(), None::<()>);
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let secret = match (state.totp_enabled, &state.totp_secret) {
+        (true, Some(secret)) => secret,
+        _ => {
+            return ApiResponder::unauthorized(ErrorMessage::TokenInvalid.to_string(), None::<()>);
+        }
+    };
+
+    let is_valid = match verify_code(secret, &state.username, &data.code) {
+        Some(step) => accept_totp_step(&pool, state.id, step).await,
+        None => use_recovery_code(&pool, state.id, &data.code).await,
+    };
+
+    let is_valid = match is_valid {
+        Ok(is_valid) => is_valid,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if !is_valid {
+        return ApiResponder::unauthorized(ErrorMessage::TotpCodeInvalid.to_string(), None::<()>);
+    }
+
+    match consume_challenge_token(&pool, &challenge).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return ApiResponder::unauthorized(ErrorMessage::TokenInvalid.to_string(), None::<()>);
+        }
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    }
+
+    if let Err(e) = clear_failed_logins(&pool, &USERNAME_POLICY, &state.username).await {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    if let Err(e) = apply_deferred_rehash(&pool, &state.username).await {
+        tracing::warn!("Failed to rehash password of {}: {}", state.username, e);
+    }
+
+    let client = ClientInfo::from_request(&req);
+
+    match create_token(&pool, &state.username, &client).await {
+        Ok(token) => token_response(token),
+        Err(e) => ApiResponder::unauthorized(
+            ErrorMessage::TokenGenerateFailed {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        ),
+    }
+}
+
+// Records the step of an accepted code, false when this or a later step was already used
+async fn accept_totp_step(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+    step: u64,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r"UPDATE users SET totp_last_step = ?
+          WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)",
+    )
+    .bind(step as i64)
+    .bind(user_id)
+    .bind(step as i64)
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+async fn use_recovery_code(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+    code: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r"UPDATE user_recovery_codes SET used_at = ?
+          WHERE user_id = ? AND code_hash = ? AND used_at IS NULL
+          LIMIT 1",
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(user_id)
+    .bind(hash_recovery_code(code))
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn fetch_totp_state(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+) -> Result<Option<TotpState>, sqlx::Error> {
+    let query = r"SELECT id, username, totp_secret, totp_enabled FROM users WHERE id = ?";
+
+    sqlx::query_as::<_, TotpState>(query)
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await
+}
+
+pub async fn fetch_totp_state_by_username(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+) -> Result<Option<TotpState>, sqlx::Error> {
+    let query =
+        r"SELECT id, username, totp_secret, totp_enabled FROM users WHERE username = ?";
+
+    sqlx::query_as::<_, TotpState>(query)
+        .bind(username)
+        .fetch_optional(pool.get_ref())
+        .await
+}
@@ -1,38 +1,52 @@
-use actix_web::{HttpRequest, Responder, web};
-use sqlx::mysql::MySqlPool;
+use actix_web::{Responder, cookie::Cookie, web};
+use nanoid::nanoid;
+use sqlx::mysql::{MySql, MySqlPool};
+use sqlx::Transaction;
 
+use crate::controllers::session::{revoke_other_sessions, revoke_user_sessions};
+use crate::middleware::auth::AuthUser;
+use crate::models::group::GroupRow;
 use crate::models::message::ErrorMessage;
-use crate::models::users::{Role, UpdateUserRequest};
-use crate::utils::jwt::extract_claims;
-use crate::utils::security::hash_password;
+use crate::models::permission::Permission;
+use crate::models::users::{
+    ChangePasswordRequest, ChangeRoleRequest, ChangeRoleResponse, CurrentUserResponse,
+    ResetPasswordResponse, Role, UpdateUserRequest, UserListQuery,
+};
+use crate::utils::password_policy::validate_password;
+use crate::utils::security::{hash_password, verify_password};
+use crate::utils::audit::{ROLE_CHANGE, USER_DEACTIVATE, USER_RESTORE, record_audit};
+use crate::utils::jwt::create_access_token;
+use crate::utils::throttle::{USERNAME_POLICY, clear_failed_logins};
+use crate::utils::token_version::forget_token_version;
 use crate::{
     models::users::{CreateUserRequest, UserResponse},
     utils::responder::ApiResponder,
 };
 
-// Get All User From Database
-pub async fn get_all_users(pool: web::Data<MySqlPool>, req: HttpRequest) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
+// Get All User From Database, deactivated users only with ?include_inactive=true
+pub async fn get_all_users(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    filter: web::Query<UserListQuery>,
+) -> impl Responder {
+    if filter.include_inactive && !claims.has_permission(Permission::UserRestore) {
+        return ApiResponder::forbidden(
+            ErrorMessage::InsufficientPermissions.to_string(),
+            None::<()>,
+        );
+    }
 
-    if Role::valid_permission(&claims.role) {
-        let query = "SELECT id, username, name, role, profile_picture, created_at FROM users";
+    let query = r"SELECT id, username, name, role, profile_picture, is_active, created_at FROM users
+                  WHERE is_active = TRUE OR ?";
 
-        let result = sqlx::query_as::<_, UserResponse>(query)
-            .fetch_all(pool.get_ref())
-            .await;
+    let result = sqlx::query_as::<_, UserResponse>(query)
+        .bind(filter.include_inactive)
+        .fetch_all(pool.get_ref())
+        .await;
 
-        match result {
-            Ok(data) => ApiResponder::success(ErrorMessage::Success.to_string(), Some(data)),
-            Err(e) => ApiResponder::<()>::handle_error(e),
-        }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+    match result {
+        Ok(data) => ApiResponder::success(ErrorMessage::Success.to_string(), Some(data)),
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
@@ -40,16 +54,11 @@ pub async fn get_all_users(pool: web::Data<MySqlPool>, req: HttpRequest) -> impl
 pub async fn get_user(
     pool: web::Data<MySqlPool>,
     path: web::Path<String>,
-    req: HttpRequest,
 ) -> impl Responder {
-    let cookies = req.cookies();
-
-    tracing::info!("{:?}", cookies);
-
     let username = path.into_inner();
 
     let result = sqlx::query_as::<_, UserResponse>(
-        "SELECT id, username, name, role, profile_picture FROM users WHERE username = ?",
+        "SELECT id, username, name, role, profile_picture, is_active FROM users WHERE username = ?",
     )
     .bind(username)
     .fetch_optional(pool.get_ref())
@@ -62,99 +71,318 @@ pub async fn get_user(
     }
 }
 
+// Get the logged in user from Claims.user_id, with groups, permissions and task counts
+pub async fn get_me(pool: web::Data<MySqlPool>, claims: AuthUser) -> impl Responder {
+    let user = sqlx::query_as::<_, UserResponse>(
+        "SELECT id, username, name, role, profile_picture, is_active FROM users WHERE id = ?",
+    )
+    .bind(claims.user_id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let user = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let groups_query = r"SELECT g.id, g.group_number, g.course, g.created_at
+                         FROM `groups` g
+                         JOIN group_members gm ON gm.group_id = g.id
+                         WHERE gm.user_id = ?
+                         ORDER BY g.course, g.group_number";
+
+    let groups = match sqlx::query_as::<_, GroupRow>(groups_query)
+        .bind(claims.user_id)
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(groups) => groups,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    // A task counts as finished when the user or one of their groups finished it
+    let counts_query = r"SELECT
+                             CAST(COALESCE(SUM(finished), 0) AS SIGNED),
+                             CAST(COALESCE(SUM(1 - finished), 0) AS SIGNED)
+                         FROM (
+                             SELECT (
+                                 EXISTS (
+                                     SELECT 1 FROM finished_user_tasks fu
+                                     WHERE fu.task_id = t.id AND fu.user_id = ?
+                                 ) OR EXISTS (
+                                     SELECT 1 FROM finished_group_tasks fg
+                                     JOIN group_members gm ON gm.group_id = fg.group_id
+                                     WHERE fg.task_id = t.id AND gm.user_id = ?
+                                 )
+                             ) AS finished
+                             FROM tasks t
+                         ) task_state";
+
+    let (finished_tasks, unfinished_tasks) = match sqlx::query_as::<_, (i64, i64)>(counts_query)
+        .bind(claims.user_id)
+        .bind(claims.user_id)
+        .fetch_one(pool.get_ref())
+        .await
+    {
+        Ok(counts) => counts,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    ApiResponder::success(
+        ErrorMessage::Success.to_string(),
+        Some(CurrentUserResponse {
+            user,
+            groups,
+            // Narrowed to the scopes when called with a personal access token
+            permissions: claims
+                .role
+                .permissions()
+                .iter()
+                .copied()
+                .filter(|permission| claims.has_permission(*permission))
+                .collect(),
+            finished_tasks,
+            unfinished_tasks,
+        }),
+    )
+}
+
 // Create user to database
 pub async fn create_user(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    claims: AuthUser,
     data: web::Json<CreateUserRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
+    // Any role above Anggota is handed out only by who may change roles
+    let is_default_role = matches!(data.role, Role::Anggota);
+
+    if !is_default_role && !claims.has_permission(Permission::UserChangeRole) {
+        return ApiResponder::forbidden(
+            ErrorMessage::InsufficientPermissions.to_string(),
+            None::<()>,
+        );
+    }
+
+    let errors = validate_password("password", &data.password, &data.username);
+
+    if !errors.is_empty() {
+        return ApiResponder::unprocessable_entity(
+            ErrorMessage::PasswordPolicyViolation.to_string(),
+            Some(errors),
+        );
+    }
+
+    let encrypted_password = match hash_password(&data.password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::PasswordHashFailed {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
     };
 
-    if Role::has_permission(&claims.role) {
-        let encrypted_password = hash_password(&data.password);
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
 
-        let query = r"INSERT INTO users (username, name, role, password) 
+    let query = r"INSERT INTO users (username, name, role, password) 
                   VALUES (?, ?, ?, ?)";
 
-        let result = sqlx::query(query)
-            .bind(&data.username)
-            .bind(&data.name)
-            .bind(&data.role.to_string())
-            .bind(encrypted_password)
-            .execute(pool.get_ref())
-            .await;
+    let result = sqlx::query(query)
+        .bind(&data.username)
+        .bind(&data.name)
+        .bind(data.role.to_string())
+        .bind(encrypted_password)
+        .execute(&mut tx)
+        .await;
 
-        match result {
-            Ok(_) => ApiResponder::created(ErrorMessage::CreateDataSuccess.to_string(), Some(data)),
-            Err(e) => ApiResponder::<()>::handle_error(e),
+    let user_id = match result {
+        Ok(res) => res.last_insert_id() as i32,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if !is_default_role {
+        let role = data.role.to_string();
+
+        if let Err(e) =
+            record_audit(&mut tx, claims.user_id, user_id, ROLE_CHANGE, None, Some(&role)).await
+        {
+            return ApiResponder::<()>::handle_error(e);
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+    }
+
+    match tx.commit().await {
+        Ok(_) => ApiResponder::created(ErrorMessage::CreateDataSuccess.to_string(), Some(data)),
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
-// Delete user from database with username
+// True when no other active Ketua would be left once this user loses the role or the account
+async fn is_last_active_ketua(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    // Locks the Ketua rows so two concurrent changes can't both pass the check
+    let other_ketua_ids = sqlx::query_scalar::<_, i32>(
+        r"SELECT id FROM users WHERE role = ? AND is_active = TRUE AND id <> ? FOR UPDATE",
+    )
+    .bind(Role::Ketua.to_string())
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    Ok(other_ketua_ids.is_empty())
+}
+
+// Deactivate a user, the row is kept so finished tasks and group history stay intact
 pub async fn delete_user(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    claims: AuthUser,
     path: web::Path<String>,
 ) -> impl Responder {
     let username = path.into_inner();
 
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
     };
 
-    if Role::has_permission(&claims.role) {
-        let query = r"Delete from users where username = ?";
-        let result = sqlx::query(query)
-            .bind(username)
-            .execute(pool.get_ref())
-            .await;
+    let (user_id, role) = match sqlx::query_as::<_, (i32, String)>(
+        r"SELECT id, role FROM users WHERE username = ? AND is_active = TRUE FOR UPDATE",
+    )
+    .bind(&username)
+    .fetch_optional(&mut tx)
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
 
-        match result {
-            Ok(res) => {
-                if res.rows_affected() == 0 {
-                    ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>)
-                } else {
-                    ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
-                }
+    if role == Role::Ketua.to_string() {
+        match is_last_active_ketua(&mut tx, user_id).await {
+            Ok(true) => {
+                return ApiResponder::conflict(ErrorMessage::LastKetua.to_string(), None::<()>);
             }
-            Err(e) => ApiResponder::<()>::handle_error(e),
+            Ok(false) => {}
+            Err(e) => return ApiResponder::<()>::handle_error(e),
         }
-    } else {
-        ApiResponder::unauthorized(
-            ErrorMessage::InsufficientPermissions.to_string(),
-            None::<()>,
-        )
+    }
+
+    let deactivated = sqlx::query(
+        r"UPDATE users SET is_active = FALSE, deleted_at = ?, token_version = token_version + 1
+          WHERE id = ?",
+    )
+    .bind(chrono::Utc::now().naive_utc())
+    .bind(user_id)
+    .execute(&mut tx)
+    .await;
+
+    if let Err(e) = deactivated {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    let revoked =
+        sqlx::query(r"UPDATE sessions SET is_revoke = TRUE WHERE user_id = ? AND is_revoke = FALSE")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = revoked {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    if let Err(e) = record_audit(
+        &mut tx,
+        claims.user_id,
+        user_id,
+        USER_DEACTIVATE,
+        Some("active"),
+        Some("inactive"),
+    )
+    .await
+    {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            forget_token_version(user_id);
+            ApiResponder::success(ErrorMessage::DeleteSuccess.to_string(), None::<()>)
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Reactivate a deactivated user, they log in again with their old password
+pub async fn restore_user(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    user_id: web::Path<i32>,
+) -> impl Responder {
+    let user_id = user_id.into_inner();
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let restored = sqlx::query(
+        r"UPDATE users SET is_active = TRUE, deleted_at = NULL WHERE id = ? AND is_active = FALSE",
+    )
+    .bind(user_id)
+    .execute(&mut tx)
+    .await;
+
+    match restored {
+        Ok(res) if res.rows_affected() == 0 => {
+            return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>);
+        }
+        Ok(_) => {}
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    }
+
+    if let Err(e) = record_audit(
+        &mut tx,
+        claims.user_id,
+        user_id,
+        USER_RESTORE,
+        Some("inactive"),
+        Some("active"),
+    )
+    .await
+    {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            forget_token_version(user_id);
+            ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>)
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
     }
 }
 
 // Update data user
 pub async fn update_data_user(
     pool: web::Data<MySqlPool>,
-    req: HttpRequest,
+    claims: AuthUser,
     data_req: web::Json<UpdateUserRequest>,
 ) -> impl Responder {
-    let claims = match extract_claims(&req).await {
-        Ok(claims) => claims,
-        Err(e) => return e,
-    };
-
-    if Role::has_permission(&claims.role) || &claims.user_id == &data_req.user_id {
-        let query = r"UPDATE users SET name = ?, profile_picture = ? WHERE username = ?";
+    if claims.has_permission(Permission::UserUpdate) || claims.user_id == data_req.user_id {
+        // The profile picture is only written by the upload endpoint
+        let query = r"UPDATE users SET name = ? WHERE id = ?";
 
         let response = sqlx::query(query)
             .bind(&data_req.name)
-            .bind(&data_req.profile_picture)
-            .bind(&data_req.username)
+            .bind(data_req.user_id)
             .execute(pool.get_ref())
             .await;
 
@@ -163,6 +391,234 @@ pub async fn update_data_user(
             Err(e) => ApiResponder::<()>::handle_error(e),
         }
     } else {
-        ApiResponder::unauthorized(ErrorMessage::UnAuthorized.to_string(), None::<()>)
+        ApiResponder::forbidden(ErrorMessage::InsufficientPermissions.to_string(), None::<()>)
+    }
+}
+
+// Change the password of the logged in user, other devices are logged out
+pub async fn change_my_password(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    data: web::Json<ChangePasswordRequest>,
+) -> impl Responder {
+    if !verify_password(&pool, &claims.sub, &data.current_password).await {
+        return ApiResponder::unauthorized(
+            ErrorMessage::CurrentPasswordInvalid.to_string(),
+            None::<()>,
+        );
+    }
+
+    let errors = validate_password("new_password", &data.new_password, &claims.sub);
+
+    if !errors.is_empty() {
+        return ApiResponder::unprocessable_entity(
+            ErrorMessage::PasswordPolicyViolation.to_string(),
+            Some(errors),
+        );
     }
-}
\ No newline at end of file
+
+    let new_password = match hash_password(&data.new_password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::PasswordHashFailed {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let query = r"UPDATE users SET password = ?, must_change_password = FALSE,
+                  token_version = token_version + 1 WHERE id = ?";
+
+    let result = sqlx::query(query)
+        .bind(new_password)
+        .bind(claims.user_id)
+        .execute(pool.get_ref())
+        .await;
+
+    if let Err(e) = result {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    forget_token_version(claims.user_id);
+
+    if let Err(e) = revoke_other_sessions(&pool, claims.user_id, &claims.sid).await {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    // The version bump invalidated the caller's token too, hand out a fresh one for this session
+    match create_access_token(&pool, claims.user_id, &claims.sid).await {
+        Ok(access_token) => {
+            let access_cookie = Cookie::build("access_token", access_token.clone())
+                .http_only(true)
+                .secure(false)
+                .path("/")
+                .finish();
+
+            ApiResponder::success_with_cookie(
+                ErrorMessage::UpdateDataSuccess.to_string(),
+                Some(serde_json::json!({ "token": access_token })),
+                vec![access_cookie],
+            )
+        }
+        Err(e) => ApiResponder::unauthorized(
+            ErrorMessage::TokenGenerateFailed {
+                details: e.to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        ),
+    }
+}
+
+// Reset a user password to a temporary one that must be changed on next login
+pub async fn reset_user_password(
+    pool: web::Data<MySqlPool>,
+    user_id: web::Path<i32>,
+) -> impl Responder {
+    let user_id = user_id.into_inner();
+    let temporary_password = nanoid!(12);
+
+    let hashed_password = match hash_password(&temporary_password) {
+        Ok(hashed) => hashed,
+        Err(e) => {
+            return ApiResponder::error(
+                ErrorMessage::PasswordHashFailed {
+                    details: e.to_string(),
+                }
+                .to_string(),
+                None::<()>,
+            );
+        }
+    };
+
+    let query = r"UPDATE users SET password = ?, must_change_password = TRUE,
+                  token_version = token_version + 1 WHERE id = ?";
+
+    let result = sqlx::query(query)
+        .bind(hashed_password)
+        .bind(user_id)
+        .execute(pool.get_ref())
+        .await;
+
+    match result {
+        Ok(res) if res.rows_affected() == 0 => {
+            return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>);
+        }
+        Ok(_) => forget_token_version(user_id),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    }
+
+    match revoke_user_sessions(&pool, user_id).await {
+        Ok(_) => ApiResponder::success(
+            ErrorMessage::UpdateDataSuccess.to_string(),
+            Some(ResetPasswordResponse {
+                user_id,
+                temporary_password,
+            }),
+        ),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Change the role of a user, there must always be at least one Ketua left
+pub async fn change_user_role(
+    pool: web::Data<MySqlPool>,
+    claims: AuthUser,
+    user_id: web::Path<i32>,
+    data: web::Json<ChangeRoleRequest>,
+) -> impl Responder {
+    let user_id = user_id.into_inner();
+    let new_role = data.role.to_string();
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    let old_role = match sqlx::query_scalar::<_, String>(
+        r"SELECT role FROM users WHERE id = ? FOR UPDATE",
+    )
+    .bind(user_id)
+    .fetch_optional(&mut tx)
+    .await
+    {
+        Ok(Some(role)) => role,
+        Ok(None) => return ApiResponder::not_found(ErrorMessage::NotFound.to_string(), None::<()>),
+        Err(e) => return ApiResponder::<()>::handle_error(e),
+    };
+
+    if old_role == new_role {
+        return ApiResponder::success(
+            ErrorMessage::UpdateDataSuccess.to_string(),
+            Some(ChangeRoleResponse {
+                user_id,
+                old_role,
+                new_role,
+            }),
+        );
+    }
+
+    if old_role == Role::Ketua.to_string() {
+        match is_last_active_ketua(&mut tx, user_id).await {
+            Ok(true) => {
+                return ApiResponder::conflict(ErrorMessage::LastKetua.to_string(), None::<()>);
+            }
+            Ok(false) => {}
+            Err(e) => return ApiResponder::<()>::handle_error(e),
+        }
+    }
+
+    let updated =
+        sqlx::query(r"UPDATE users SET role = ?, token_version = token_version + 1 WHERE id = ?")
+            .bind(&new_role)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await;
+
+    if let Err(e) = updated {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    if let Err(e) = record_audit(
+        &mut tx,
+        claims.user_id,
+        user_id,
+        ROLE_CHANGE,
+        Some(&old_role),
+        Some(&new_role),
+    )
+    .await
+    {
+        return ApiResponder::<()>::handle_error(e);
+    }
+
+    match tx.commit().await {
+        Ok(_) => {
+            forget_token_version(user_id);
+
+            ApiResponder::success(
+                ErrorMessage::UpdateDataSuccess.to_string(),
+                Some(ChangeRoleResponse {
+                    user_id,
+                    old_role,
+                    new_role,
+                }),
+            )
+        }
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
+
+// Lift the login lockout of a user after too many failed attempts
+pub async fn unlock_user(pool: web::Data<MySqlPool>, path: web::Path<String>) -> impl Responder {
+    let username = path.into_inner();
+
+    match clear_failed_logins(&pool, &USERNAME_POLICY, &username).await {
+        Ok(_) => ApiResponder::success(ErrorMessage::UpdateDataSuccess.to_string(), None::<()>),
+        Err(e) => ApiResponder::<()>::handle_error(e),
+    }
+}
@@ -1,9 +1,12 @@
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, http::header, web};
-use config::{mysql::establish_mysql_connection};
+use config::{
+    jwt::init_jwt_keys, mysql::establish_mysql_connection, origin::allowed_origins,
+    proxy::init_trusted_proxies,
+};
+use utils::{notifier::notifier_from_env, security::init_dummy_hash, storage::storage_from_env};
 use dotenv::dotenv;
 use env_logger::Env;
-use middleware::auth::AuthMiddleware;
 use std::env;
 
 mod config;
@@ -27,11 +30,16 @@ async fn main() -> std::io::Result<()> {
 
     let mysql_conn = establish_mysql_connection().await;
 
-    let secret_key = env::var("SECRET_KEY").unwrap();
+    init_jwt_keys();
+    init_trusted_proxies();
+    init_dummy_hash();
+    let notifier = notifier_from_env().map(web::Data::from);
+    let storage = web::Data::from(storage_from_env());
 
     HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000") // change with your domain 
+        let cors = allowed_origins()
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin)) // set ALLOWED_ORIGINS with your domain
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
             .allowed_headers(vec![header::AUTHORIZATION, header::CONTENT_TYPE])
             .supports_credentials();
@@ -39,19 +47,14 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .wrap(cors)
             .app_data(web::Data::new(mysql_conn.clone()))
+            .app_data(storage.clone())
             // .app_data(web::Data::new(mongodb_conn.clone()))
-            .configure(routes::auth::config)
-            .configure(routes::session::config)
-            .configure(routes::users::config)
-            .service(
-                web::scope("/api")
-                    .wrap(AuthMiddleware::new(secret_key.clone()))
-                    .configure(routes::tasks::config)
-                    .configure(routes::course::config)
-                    .configure(routes::user_tasks::config)
-                    .configure(routes::group_tasks::config)
-                    .configure(routes::group::config),
-            )
+            .configure(|cfg| {
+                if let Some(notifier) = &notifier {
+                    cfg.app_data(notifier.clone());
+                }
+            })
+            .configure(routes::config)
     })
     .bind((server_host, server_port))?
     .run()
@@ -1,29 +1,73 @@
 use actix_web::{
-    Error,
+    Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
     body::BoxBody,
-    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    dev::{Payload, Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+    error::InternalError,
+    http::Method,
+    web,
 };
-use futures_util::future::{LocalBoxFuture, Ready, ok};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, errors::ErrorKind};
-use std::rc::Rc;
+use futures_util::future::{LocalBoxFuture, Ready, ok, ready};
+use sqlx::MySqlPool;
+use std::{ops::Deref, rc::Rc};
 
 use crate::{
-    models::{auth::Claims, message::ErrorMessage},
-    utils::responder::ApiResponder,
+    controllers::personal_token::{PERSONAL_TOKEN_PREFIX, resolve_personal_token},
+    models::{
+        auth::Claims,
+        message::ErrorMessage,
+        personal_token::{SCOPE_READ, SCOPE_WRITE},
+    },
+    utils::{
+        jwt::{decode_token, extract_claims, read_access_token},
+        responder::ApiResponder,
+    },
 };
 
-pub struct AuthMiddleware {
-    secret: Rc<String>,
-}
+const CHANGE_PASSWORD_PATH: &str = "/api/users/me/password";
+const TOTP_PATH_PREFIX: &str = "/api/users/me/totp";
+
+// Credential management, never reachable with a personal access token
+const CREDENTIAL_PATH_PREFIXES: &[&str] = &[
+    CHANGE_PASSWORD_PATH,
+    TOTP_PATH_PREFIX,
+    "/api/users/me/identities",
+    "/api/tokens",
+];
+
+// Tokens flagged at login only reach the endpoint that clears the flag
+fn pending_action(claims: &Claims, method: &Method, path: &str) -> Option<ErrorMessage> {
+    if claims.scopes.is_some() {
+        // Personal access tokens never manage credentials, including other tokens
+        if CREDENTIAL_PATH_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            return Some(ErrorMessage::PersonalTokenNotAllowed);
+        }
 
-impl AuthMiddleware {
-    pub fn new(secret: String) -> Self {
-        AuthMiddleware {
-            secret: Rc::new(secret),
+        let is_read = matches!(*method, Method::GET | Method::HEAD);
+        if !claims.has_scope(if is_read { SCOPE_READ } else { SCOPE_WRITE }) {
+            return Some(if is_read {
+                ErrorMessage::InsufficientPermissions
+            } else {
+                ErrorMessage::PersonalTokenReadOnly
+            });
         }
     }
+
+    if claims.must_change_password && path != CHANGE_PASSWORD_PATH {
+        return Some(ErrorMessage::PasswordChangeRequired);
+    }
+
+    if claims.must_enroll_totp && !path.starts_with(TOTP_PATH_PREFIX) {
+        return Some(ErrorMessage::TotpEnrollmentRequired);
+    }
+
+    None
 }
 
+pub struct AuthMiddleware;
+
 impl<S> Transform<S, ServiceRequest> for AuthMiddleware
 where
     S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
@@ -36,15 +80,13 @@ where
 
     fn new_transform(&self, service: S) -> Self::Future {
         ok(AuthMiddlewareMiddleware {
-            service,
-            secret: Rc::clone(&self.secret),
+            service: Rc::new(service),
         })
     }
 }
 
 pub struct AuthMiddlewareMiddleware<S> {
-    service: S,
-    secret: Rc<String>,
+    service: Rc<S>,
 }
 
 impl<S> Service<ServiceRequest> for AuthMiddlewareMiddleware<S>
@@ -58,61 +100,138 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
-        let secret = Rc::clone(&self.secret);
-        let auth_header = req.headers().get("Authorization").cloned();
-
-        if let Some(auth_value) = auth_header {
-            if let Ok(auth_str) = auth_value.to_str() {
-                if auth_str.starts_with("Bearer ") {
-                    let token = &auth_str[7..];
-                    let validation = Validation::new(Algorithm::HS256);
-                    let result = decode::<Claims>(
-                        token,
-                        &DecodingKey::from_secret(secret.as_bytes()),
-                        &validation,
-                    );
-
-                    match result {
-                        Ok(_) => {
-                            let fut = self.service.call(req);
-                            return Box::pin(async move { fut.await });
-                        }
-                        Err(e) => match e.kind() {
-                            ErrorKind::ExpiredSignature => {
-                                let expired_response = ApiResponder::unauthorized(
-                                    "Token expired".to_string(),
-                                    None::<()>,
-                                );
-                                return Box::pin(
-                                    async move { Ok(req.into_response(expired_response)) },
-                                );
-                            }
-                            ErrorKind::InvalidToken => {
-                                let invalid_response = ApiResponder::unauthorized(
-                                    "Invalid token".to_string(),
-                                    None::<()>,
-                                );
-                                return Box::pin(
-                                    async move { Ok(req.into_response(invalid_response)) },
-                                );
-                            }
-                            _ => {
-                                let generic_error_response = ApiResponder::unauthorized(
-                                    "Token validation error".to_string(),
-                                    None::<()>,
-                                );
-                                return Box::pin(async move {
-                                    Ok(req.into_response(generic_error_response))
-                                });
-                            }
-                        },
-                    }
+        let service = Rc::clone(&self.service);
+
+        let token = match read_access_token(req.request()) {
+            Ok(token) => token,
+            Err(response) => return Box::pin(async move { Ok(req.into_response(response)) }),
+        };
+
+        Box::pin(async move {
+            let claims = if token.starts_with(PERSONAL_TOKEN_PREFIX) {
+                match authenticate_personal_token(&req, &token).await {
+                    Ok(claims) => claims,
+                    Err(response) => return Ok(req.into_response(response)),
+                }
+            } else {
+                let decoded = match database_pool(&req) {
+                    Ok(pool) => decode_token(pool, &token).await,
+                    Err(response) => Err(response),
+                };
+
+                match decoded {
+                    Ok(claims) => claims,
+                    Err(response) => return Ok(req.into_response(response)),
                 }
+            };
+
+            if let Some(pending) = pending_action(&claims, req.method(), req.path()) {
+                let pending_response = ApiResponder::forbidden(pending.to_string(), None::<()>);
+                return Ok(req.into_response(pending_response));
             }
+
+            req.extensions_mut().insert(claims);
+            service.call(req).await
+        })
+    }
+}
+
+// Personal access tokens are looked up in the database instead of being decoded
+async fn authenticate_personal_token(
+    req: &ServiceRequest,
+    token: &str,
+) -> Result<Claims, HttpResponse> {
+    match resolve_personal_token(database_pool(req)?, token).await {
+        Ok(Some(claims)) => Ok(claims),
+        Ok(None) => Err(ApiResponder::unauthorized(
+            ErrorMessage::TokenInvalid.to_string(),
+            None::<()>,
+        )),
+        Err(e) => Err(ApiResponder::<()>::handle_error(e)),
+    }
+}
+
+fn database_pool(req: &ServiceRequest) -> Result<&web::Data<MySqlPool>, HttpResponse> {
+    req.app_data::<web::Data<MySqlPool>>().ok_or_else(|| {
+        ApiResponder::error(
+            ErrorMessage::Error {
+                details: "Database pool is not configured".to_string(),
+            }
+            .to_string(),
+            None::<()>,
+        )
+    })
+}
+
+// Authenticated user of the request, taken from the claims stored by AuthMiddleware
+pub struct AuthUser(pub Claims);
+
+impl Deref for AuthUser {
+    type Target = Claims;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            extract_claims(req)
+                .map(AuthUser)
+                .map_err(|response| InternalError::from_response("", response).into()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::users::Role;
+
+    fn claims(scopes: Option<&[&str]>) -> Claims {
+        Claims {
+            sub: "student42".to_string(),
+            user_id: 1,
+            role: Role::Ketua,
+            sid: "session".to_string(),
+            must_change_password: false,
+            must_enroll_totp: false,
+            scopes: scopes.map(|scopes| scopes.iter().map(|s| s.to_string()).collect()),
+            ver: 0,
+            iss: "issuer".to_string(),
+            aud: "audience".to_string(),
+            iat: 0,
+            jti: "jti".to_string(),
+            exp: 0,
+        }
+    }
+
+    #[test]
+    fn personal_tokens_never_reach_credential_management() {
+        let token = claims(Some(&[SCOPE_READ, SCOPE_WRITE]));
+
+        for (method, path) in [
+            (Method::POST, "/api/users/me/password"),
+            (Method::POST, "/api/users/me/totp/enroll"),
+            (Method::DELETE, "/api/users/me/totp"),
+            (Method::POST, "/api/users/me/identities/oidc"),
+            (Method::GET, "/api/tokens"),
+            (Method::DELETE, "/api/tokens/1"),
+        ] {
+            assert!(
+                matches!(
+                    pending_action(&token, &method, path),
+                    Some(ErrorMessage::PersonalTokenNotAllowed)
+                ),
+                "{method} {path}"
+            );
+            assert!(pending_action(&claims(None), &method, path).is_none(), "{method} {path}");
         }
 
-        let unauthorized_response =
-            ApiResponder::unauthorized(ErrorMessage::TokenInvalid.to_string(), None::<()>);
-        Box::pin(async move { Ok(req.into_response(unauthorized_response)) })
+        assert!(pending_action(&token, &Method::POST, "/api/tasks").is_none());
     }
 }
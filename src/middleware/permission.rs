@@ -0,0 +1,73 @@
+use actix_web::{
+    Error,
+    body::BoxBody,
+    dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready},
+};
+use futures_util::future::{LocalBoxFuture, Ready, ok};
+
+use crate::{
+    models::{message::ErrorMessage, permission::Permission},
+    utils::{jwt::extract_claims, responder::ApiResponder},
+};
+
+// Route guard rejecting users whose role lacks the given permission
+pub struct RequirePermission {
+    permission: Permission,
+}
+
+impl RequirePermission {
+    pub fn new(permission: Permission) -> Self {
+        RequirePermission { permission }
+    }
+}
+
+impl<S> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequirePermissionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequirePermissionMiddleware {
+            service,
+            permission: self.permission,
+        })
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: S,
+    permission: Permission,
+}
+
+impl<S> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<BoxBody>, Error = Error> + 'static,
+{
+    type Response = ServiceResponse<BoxBody>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let claims = match extract_claims(req.request()) {
+            Ok(claims) => claims,
+            Err(response) => return Box::pin(async move { Ok(req.into_response(response)) }),
+        };
+
+        if claims.has_permission(self.permission) {
+            return Box::pin(self.service.call(req));
+        }
+
+        let forbidden_response = ApiResponder::forbidden(
+            ErrorMessage::InsufficientPermissions.to_string(),
+            None::<()>,
+        );
+        Box::pin(async move { Ok(req.into_response(forbidden_response)) })
+    }
+}
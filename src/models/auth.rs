@@ -1,19 +1,64 @@
 use serde::{Deserialize, Serialize};
 
-use super::users::Role;
+use super::{permission::Permission, users::Role};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub user_id: i32,
     pub role: Role,
+    pub sid: String,
+    #[serde(default)]
+    pub must_change_password: bool,
+    #[serde(default)]
+    pub must_enroll_totp: bool,
+    // Only set for personal access tokens, None means the full role
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    // users.token_version when issued, a newer version in the database rejects the token
+    #[serde(default)]
+    pub ver: i32,
+    pub iss: String,
+    pub aud: String,
+    pub iat: usize,
+    // Unique per access token, checked against the revoked_access_tokens deny-list
+    pub jti: String,
+    pub exp: usize,
+}
+
+impl Claims {
+    // Role permission, narrowed by the scopes of a personal access token
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.role.has_permission(permission)
+            && self.scopes.as_ref().is_none_or(|scopes| {
+                scopes.iter().any(|scope| scope == permission.as_str())
+            })
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.iter().any(|s| s == scope))
+    }
+}
+
+// Short-lived proof that the password was verified, exchanged for a Token with a TOTP code
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChallengeClaims {
+    pub sub: String,
+    pub user_id: i32,
+    pub purpose: String,
+    pub iss: String,
+    pub aud: String,
+    pub jti: String,
     pub exp: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Token {
     pub access_token: String,
-    pub refresh_token: String
+    pub refresh_token: String,
+    pub must_change_password: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,9 +66,3 @@ pub struct LoginRequest {
     pub username: String,
     pub password: String
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LogoutRequest {
-    
-}
-
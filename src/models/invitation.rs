@@ -0,0 +1,39 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::users::Role;
+
+#[derive(Deserialize)]
+pub struct CreateInvitationRequest {
+    pub role: Role,
+    pub expires_in_days: Option<i64>,
+}
+
+// Returned once on creation, the plain code is never stored
+#[derive(Serialize)]
+pub struct CreatedInvitationResponse {
+    pub id: i32,
+    pub code: String,
+    pub role: Role,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct InvitationResponse {
+    pub id: i32,
+    pub role: String,
+    pub created_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub used_by: Option<i32>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct RedeemInvitationRequest {
+    pub code: String,
+    pub username: String,
+    pub name: String,
+    pub password: String,
+}
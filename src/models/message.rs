@@ -2,26 +2,50 @@ use std::fmt::{self, Display};
 
 #[derive(Debug)]
 pub enum ErrorMessage {
-    Authorized,
     CantBeNull,
+    CsrfCheckFailed,
+    CurrentPasswordInvalid,
     CreateDataSuccess,
     DeleteSuccess,
     Duplicate,
+    IdentityAlreadyLinked,
     InsufficientPermissions,
     InvalidAuthHeader,
     InvalidAuthScheme,
+    InvitationInvalid,
+    LastKetua,
     LoginInvalid,
     LoginSuccess,
+    LoginThrottled,
     LogoutSuccess,
     NoAuthHeader,
     NotFound,
+    OidcAccountNotLinked,
+    OidcNotConfigured,
+    OidcStateInvalid,
+    PasswordContainsUsername,
+    PasswordPolicyViolation,
+    PasswordResetRequested,
+    PasswordResetNotConfigured,
+    PasswordResetTokenInvalid,
+    PasswordTooCommon,
+    PasswordChangeRequired,
+    PersonalTokenNotAllowed,
+    PersonalTokenReadOnly,
+    ProfilePictureMissing,
+    ProfilePictureUnsupported,
     RefreshTokenInvalid,
     Success,
     TokenInvalid,
+    TokenOutdated,
+    TotpAlreadyEnabled,
+    TotpCodeInvalid,
+    TotpEnrollmentRequired,
+    TotpNotEnrolled,
+    TotpRequiredForRole,
+    TwoFactorRequired,
     UnAuthorized,
     UpdateDataSuccess,
-    UserAlreadyInGroup,
-    UserNotMatch,
 
     // 🔽 New basic error types
     DataTooLong,
@@ -31,9 +55,14 @@ pub enum ErrorMessage {
     // 🔽 Error with details
     DatabaseError { details: String },
     Error { details: String },
-    FailedAddMember { details: String },
     FailedFetchFinishedTask { details: String },
     FailedFetchUnFinishedTask { details: String },
+    OidcLoginFailed { details: String },
+    PasswordHashFailed { details: String },
+    PasswordTooShort { min_length: usize },
+    PersonalTokenScopeInvalid { details: String },
+    ProfilePictureInvalid { details: String },
+    ProfilePictureTooLarge { max_bytes: usize },
     TaskTypeError { details: String },
     TokenDecodeError { details: String },
     TokenGenerateFailed { details: String },
@@ -44,28 +73,86 @@ impl Display for ErrorMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             // 🔁 Basic messages
-            ErrorMessage::Authorized => write!(f, "Authorized"),
             ErrorMessage::CantBeNull => write!(f, "Can't be null"),
+            ErrorMessage::CsrfCheckFailed => write!(f, "Request origin is not allowed"),
+            ErrorMessage::CurrentPasswordInvalid => write!(f, "Current password is wrong"),
             ErrorMessage::CreateDataSuccess => write!(f, "Create data success"),
             ErrorMessage::DeleteSuccess => write!(f, "Delete data success"),
             ErrorMessage::Duplicate => write!(f, "Data duplicated"),
+            ErrorMessage::IdentityAlreadyLinked => {
+                write!(f, "This identity is already linked to another user")
+            }
             ErrorMessage::InsufficientPermissions => {
                 write!(f, "Insufficient permissions for this action")
             }
             ErrorMessage::InvalidAuthHeader => write!(f, "Invalid authorization header"),
             ErrorMessage::InvalidAuthScheme => write!(f, "Invalid authorization scheme"),
+            ErrorMessage::InvitationInvalid => {
+                write!(f, "Invitation is invalid, expired or already used")
+            }
+            ErrorMessage::LastKetua => write!(f, "The last active Ketua can't be demoted or deactivated"),
             ErrorMessage::LoginInvalid => write!(f, "Username or password is wrong"),
             ErrorMessage::LoginSuccess => write!(f, "Login successful"),
+            ErrorMessage::LoginThrottled => {
+                write!(f, "Too many failed login attempts, try again later")
+            }
             ErrorMessage::LogoutSuccess => write!(f, "Logout successful"),
             ErrorMessage::NoAuthHeader => write!(f, "No authorization header provided"),
             ErrorMessage::NotFound => write!(f, "Data not found"),
+            ErrorMessage::OidcAccountNotLinked => {
+                write!(f, "No account is linked to this identity")
+            }
+            ErrorMessage::OidcNotConfigured => write!(f, "Single sign-on is not configured"),
+            ErrorMessage::OidcStateInvalid => write!(f, "Login request is invalid or expired"),
+            ErrorMessage::PasswordContainsUsername => {
+                write!(f, "Password must not contain the username")
+            }
+            ErrorMessage::PasswordPolicyViolation => {
+                write!(f, "Password does not meet the password policy")
+            }
+            ErrorMessage::PasswordResetRequested => {
+                write!(f, "If the account exists, a reset link has been sent")
+            }
+            ErrorMessage::PasswordResetNotConfigured => {
+                write!(f, "Password reset is not configured on this server")
+            }
+            ErrorMessage::PasswordResetTokenInvalid => {
+                write!(f, "Password reset token is invalid or expired")
+            }
+            ErrorMessage::PasswordTooCommon => write!(f, "Password is too common"),
+            ErrorMessage::PasswordChangeRequired => {
+                write!(f, "Password must be changed before continuing")
+            }
+            ErrorMessage::PersonalTokenNotAllowed => {
+                write!(f, "Personal access tokens cannot be used for this action")
+            }
+            ErrorMessage::PersonalTokenReadOnly => {
+                write!(f, "Personal access token lacks the write scope")
+            }
+            ErrorMessage::ProfilePictureMissing => {
+                write!(f, "Upload the picture in a multipart field named file")
+            }
+            ErrorMessage::ProfilePictureUnsupported => {
+                write!(f, "Profile picture must be a PNG, JPEG or WebP image")
+            }
             ErrorMessage::RefreshTokenInvalid => write!(f, "Refresh token invalid"),
             ErrorMessage::Success => write!(f, "Success"),
             ErrorMessage::TokenInvalid => write!(f, "Token invalid"),
+            ErrorMessage::TokenOutdated => {
+                write!(f, "Token is outdated, refresh it or log in again")
+            }
+            ErrorMessage::TotpAlreadyEnabled => write!(f, "Two-factor authentication already enabled"),
+            ErrorMessage::TotpCodeInvalid => write!(f, "Authentication code is wrong"),
+            ErrorMessage::TotpEnrollmentRequired => {
+                write!(f, "Two-factor authentication must be enabled before continuing")
+            }
+            ErrorMessage::TotpNotEnrolled => write!(f, "Two-factor enrolment has not been started"),
+            ErrorMessage::TotpRequiredForRole => {
+                write!(f, "Two-factor authentication is required for this role")
+            }
+            ErrorMessage::TwoFactorRequired => write!(f, "Two-factor authentication required"),
             ErrorMessage::UnAuthorized => write!(f, "Unauthorized"),
             ErrorMessage::UpdateDataSuccess => write!(f, "Update data successfully"),
-            ErrorMessage::UserAlreadyInGroup => write!(f, "Some user already in group"),
-            ErrorMessage::UserNotMatch => write!(f, "User not match"),
 
             // ✅ Newly added
             ErrorMessage::DataTooLong => write!(f, "Data too long for column"),
@@ -75,15 +162,30 @@ impl Display for ErrorMessage {
             // 🧩 With details
             ErrorMessage::DatabaseError { details } => write!(f, "Database error: {}", details),
             ErrorMessage::Error { details } => write!(f, "Error: {}", details),
-            ErrorMessage::FailedAddMember { details } => {
-                write!(f, "Failed to add member: {}", details)
-            }
             ErrorMessage::FailedFetchFinishedTask { details } => {
                 write!(f, "Failed to fetch finished task: {}", details)
             }
             ErrorMessage::FailedFetchUnFinishedTask { details } => {
                 write!(f, "Failed to fetch unfinished task: {}", details)
             }
+            ErrorMessage::OidcLoginFailed { details } => {
+                write!(f, "Single sign-on failed: {}", details)
+            }
+            ErrorMessage::PasswordHashFailed { details } => {
+                write!(f, "Failed to hash password: {}", details)
+            }
+            ErrorMessage::PasswordTooShort { min_length } => {
+                write!(f, "Password must be at least {} characters", min_length)
+            }
+            ErrorMessage::PersonalTokenScopeInvalid { details } => {
+                write!(f, "Invalid personal access token scope: {}", details)
+            }
+            ErrorMessage::ProfilePictureInvalid { details } => {
+                write!(f, "Profile picture could not be read: {}", details)
+            }
+            ErrorMessage::ProfilePictureTooLarge { max_bytes } => {
+                write!(f, "Profile picture must not be larger than {} bytes", max_bytes)
+            }
             ErrorMessage::TaskTypeError { details } => {
                 write!(f, "Task type error: {}", details)
             }
@@ -7,4 +7,11 @@ pub mod group;
 pub mod auth;
 pub mod message;
 pub mod session;
-pub mod course;
\ No newline at end of file
+pub mod course;
+pub mod permission;
+pub mod password_reset;
+pub mod two_factor;
+pub mod personal_token;
+pub mod oidc;
+pub mod invitation;
+pub mod profile_picture;
\ No newline at end of file
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+// Query string the identity provider redirects back with
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct OidcLoginState {
+    pub pkce_verifier: String,
+    pub nonce: String,
+    pub link_user_id: Option<i32>,
+}
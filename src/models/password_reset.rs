@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetNotice {
+    pub user_id: i32,
+    pub username: String,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
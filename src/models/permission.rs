@@ -0,0 +1,116 @@
+use serde::Serialize;
+use std::fmt;
+
+use super::users::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Permission {
+    #[serde(rename = "course.create")]
+    CourseCreate,
+    #[serde(rename = "group.manage")]
+    GroupManage,
+    #[serde(rename = "session.manage")]
+    SessionManage,
+    #[serde(rename = "task.create")]
+    TaskCreate,
+    #[serde(rename = "task.delete")]
+    TaskDelete,
+    #[serde(rename = "task.update")]
+    TaskUpdate,
+    #[serde(rename = "user.change_role")]
+    UserChangeRole,
+    #[serde(rename = "user.create")]
+    UserCreate,
+    #[serde(rename = "user.delete")]
+    UserDelete,
+    #[serde(rename = "user.invite")]
+    UserInvite,
+    #[serde(rename = "user.restore")]
+    UserRestore,
+    #[serde(rename = "user.reset_password")]
+    UserResetPassword,
+    #[serde(rename = "user.unlock")]
+    UserUnlock,
+    #[serde(rename = "user.update")]
+    UserUpdate,
+}
+
+// Role to permission matrix, the only place deciding what each role may do
+const KETUA_PERMISSIONS: &[Permission] = &[
+    Permission::CourseCreate,
+    Permission::GroupManage,
+    Permission::SessionManage,
+    Permission::TaskCreate,
+    Permission::TaskDelete,
+    Permission::TaskUpdate,
+    Permission::UserChangeRole,
+    Permission::UserCreate,
+    Permission::UserDelete,
+    Permission::UserInvite,
+    Permission::UserResetPassword,
+    Permission::UserRestore,
+    Permission::UserUnlock,
+    Permission::UserUpdate,
+];
+
+const SEKRETARIS_PERMISSIONS: &[Permission] = &[
+    Permission::CourseCreate,
+    Permission::GroupManage,
+    Permission::TaskCreate,
+    Permission::TaskDelete,
+    Permission::TaskUpdate,
+    Permission::UserCreate,
+    Permission::UserInvite,
+    Permission::UserUpdate,
+];
+
+const ANGGOTA_PERMISSIONS: &[Permission] = &[];
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::CourseCreate => "course.create",
+            Permission::GroupManage => "group.manage",
+            Permission::SessionManage => "session.manage",
+            Permission::TaskCreate => "task.create",
+            Permission::TaskDelete => "task.delete",
+            Permission::TaskUpdate => "task.update",
+            Permission::UserChangeRole => "user.change_role",
+            Permission::UserCreate => "user.create",
+            Permission::UserDelete => "user.delete",
+            Permission::UserInvite => "user.invite",
+            Permission::UserResetPassword => "user.reset_password",
+            Permission::UserRestore => "user.restore",
+            Permission::UserUnlock => "user.unlock",
+            Permission::UserUpdate => "user.update",
+        }
+    }
+
+    // Ketua holds every permission, so its list doubles as the full set
+    pub fn from_name(name: &str) -> Option<Permission> {
+        KETUA_PERMISSIONS
+            .iter()
+            .copied()
+            .find(|permission| permission.as_str() == name)
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Role {
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::Ketua => KETUA_PERMISSIONS,
+            Role::Sekretaris => SEKRETARIS_PERMISSIONS,
+            Role::Anggota => ANGGOTA_PERMISSIONS,
+        }
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
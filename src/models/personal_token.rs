@@ -0,0 +1,47 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+// Allows GET requests, every token needs it to be useful
+pub const SCOPE_READ: &str = "read";
+// Allows POST, PUT and DELETE requests
+pub const SCOPE_WRITE: &str = "write";
+
+#[derive(Deserialize)]
+pub struct CreatePersonalTokenRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_in_days: Option<i64>,
+}
+
+// Returned once on creation, the plain token is never stored
+#[derive(Serialize)]
+pub struct CreatedPersonalTokenResponse {
+    pub id: i32,
+    pub name: String,
+    pub token: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PersonalTokenResponse {
+    pub id: i32,
+    pub name: String,
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PersonalTokenOwner {
+    pub id: i32,
+    pub user_id: i32,
+    pub scopes: String,
+    pub expires_at: Option<NaiveDateTime>,
+    pub username: String,
+    pub role: String,
+    pub must_change_password: bool,
+    pub totp_enabled: bool,
+    pub token_version: i32,
+}
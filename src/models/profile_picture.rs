@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct ProfilePictureSize {
+    pub size: u32,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfilePictureResponse {
+    pub profile_picture: String,
+    pub sizes: Vec<ProfilePictureSize>,
+}
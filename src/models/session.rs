@@ -1,12 +1,16 @@
+use actix_web::HttpRequest;
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
+use crate::config::proxy::client_ip;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateSessionRequest {
     pub session_id: String,
     pub user_id: i32,
     pub refresh_token: String,
     pub user_agent: String,
+    pub ip_address: Option<String>,
     pub expires_at: NaiveDateTime,
 }
 
@@ -14,4 +18,58 @@ pub struct CreateSessionRequest {
 pub struct RevokeSessionRequest {
     pub session_id: String,
     pub is_revoke: bool
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SessionRow {
+    pub session_id: String,
+    pub user_id: i32,
+    pub refresh_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SessionResponse {
+    pub session_id: String,
+    pub user_agent: String,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+    #[sqlx(default)]
+    pub is_current: bool,
+}
+
+// Device information captured when a session is created
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub user_agent: String,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get("User-Agent")
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("unknown")
+            .chars()
+            .take(255)
+            .collect();
+
+        let forwarded_for = req
+            .headers()
+            .get("X-Forwarded-For")
+            .and_then(|value| value.to_str().ok());
+
+        let ip_address = req
+            .peer_addr()
+            .map(|addr| client_ip(addr.ip(), forwarded_for).to_string());
+
+        ClientInfo {
+            user_agent,
+            ip_address,
+        }
+    }
+}
@@ -6,10 +6,15 @@ pub enum Status {
     Created = 201,
     BadRequest = 400,
     UnAuthorized = 401,
+    Forbidden = 403,
     NotFound = 404,
     Conflict = 409,
+    PayloadTooLarge = 413,
+    UnsupportedMediaType = 415,
     UnprocessableEntity = 422,
-    InternalServerError = 500
+    TooManyRequests = 429,
+    InternalServerError = 500,
+    ServiceUnavailable = 503
 }
 
 impl From<Status> for i32 {
@@ -19,10 +24,15 @@ impl From<Status> for i32 {
             Status::Created => 201,
             Status::BadRequest => 400,
             Status::UnAuthorized => 401,
+            Status::Forbidden => 403,
             Status::NotFound => 404,
             Status::Conflict => 409,
+            Status::PayloadTooLarge => 413,
+            Status::UnsupportedMediaType => 415,
             Status::UnprocessableEntity => 422,
+            Status::TooManyRequests => 429,
             Status::InternalServerError => 500,
+            Status::ServiceUnavailable => 503,
         }
     }
 }
\ No newline at end of file
@@ -35,22 +35,6 @@ pub struct FinishedTaskRequest {
     pub task_id: i32,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow)]
-pub struct FinishedTaskResponse {
-    pub user_id: i32,
-    pub task_id: i32,
-    pub finished_at: NaiveDateTime,
-}
-
-#[derive(Serialize, sqlx::FromRow)]
-pub struct UserTaskStatus {
-    pub user_id: i32,
-    pub username: String,
-    pub name: String,
-    pub task_finished: bool,
-    pub finished_at: Option<NaiveDateTime>,
-}
-
 #[derive(Serialize, sqlx::FromRow)]
 pub struct UserTaskStatusResponse {
     pub task_id: i32,
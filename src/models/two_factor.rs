@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize)]
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpDisableRequest {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpLoginRequest {
+    pub challenge_token: String,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct TotpState {
+    pub id: i32,
+    pub username: String,
+    pub totp_secret: Option<String>,
+    pub totp_enabled: bool,
+}
@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use super::group::GroupRow;
+use super::permission::Permission;
+
 #[derive(Serialize, Deserialize)]
 pub struct CreateUserRequest {
     pub username: String,
@@ -16,9 +19,27 @@ pub struct UserResponse {
     pub name: String,
     pub role: String,
     pub profile_picture: Option<String>,
+    pub is_active: bool,
+}
+
+// The logged in user with everything a frontend needs after login
+#[derive(Serialize)]
+pub struct CurrentUserResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub groups: Vec<GroupRow>,
+    pub permissions: Vec<Permission>,
+    pub finished_tasks: i64,
+    pub unfinished_tasks: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize, sqlx::Type)]
+#[derive(Deserialize)]
+pub struct UserListQuery {
+    #[serde(default)]
+    pub include_inactive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "VARCHAR")]
 #[sqlx(rename_all = "PascalCase")]
 pub enum Role {
@@ -30,9 +51,31 @@ pub enum Role {
 #[derive(Serialize, Deserialize)]
 pub struct UpdateUserRequest {
     pub user_id: i32,
-    pub username: String,
     pub name: String,
-    pub profile_picture: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Serialize)]
+pub struct ChangeRoleResponse {
+    pub user_id: i32,
+    pub old_role: String,
+    pub new_role: String,
+}
+
+#[derive(Serialize)]
+pub struct ResetPasswordResponse {
+    pub user_id: i32,
+    pub temporary_password: String,
 }
 
 impl fmt::Display for Role {
@@ -45,18 +88,3 @@ impl fmt::Display for Role {
         write!(f, "{}", s)
     }
 }
-
-impl Role {
-    pub fn has_permission(&self) -> bool {
-        match self {
-            Role::Ketua | Role::Sekretaris => true,
-            _ => false,
-        }
-    }
-
-    pub fn valid_permission(&self) -> bool {
-        match self {
-            Role::Ketua | Role::Sekretaris | Role::Anggota => true,
-        }
-    }
-}
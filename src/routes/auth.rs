@@ -1,5 +1,5 @@
 use actix_web::web;
-use crate::controllers::auth;
+use crate::controllers::{auth, invitation, password_reset, two_factor};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,7 +8,12 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         
         //Post Method
         .route("/login", web::post().to(auth::login_handler))
+        .route("/login/totp", web::post().to(two_factor::verify_totp_login))
         .route("/logout", web::post().to(auth::logout_handler))
+        .route("/logout-all", web::post().to(auth::logout_all_handler))
+        .route("/forgot-password", web::post().to(password_reset::forgot_password))
+        .route("/reset-password", web::post().to(password_reset::reset_password))
+        .route("/invitations/redeem", web::post().to(invitation::redeem_invitation))
 
         // Delete Method
     );
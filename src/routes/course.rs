@@ -1,5 +1,7 @@
 use actix_web::web;
 use crate::controllers::course;
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,6 +10,6 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .route("", web::get().to(course::get_all_subject))
 
         // Post Method
-        .route("", web::post().to(course::create_subject))
+        .route("", web::post().to(course::create_subject).wrap(RequirePermission::new(Permission::CourseCreate)))
     );
 }
@@ -1,5 +1,7 @@
 use actix_web::web;
 use crate::controllers::group;
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,11 +10,11 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .route("", web::get().to(group::get_all_groups))
         
         //Post Method
-        .route("", web::post().to(group::create_grup))
-        .route("{id}/members", web::post().to(group::add_member_to_group))
+        .route("", web::post().to(group::create_grup).wrap(RequirePermission::new(Permission::GroupManage)))
+        .route("{id}/members", web::post().to(group::add_member_to_group).wrap(RequirePermission::new(Permission::GroupManage)))
 
         // Delete Method
-        .route("{id}", web::delete().to(group::delete_group))
-        .route("{id}/members", web::delete().to(group::remove_member_from_group))
+        .route("{id}", web::delete().to(group::delete_group).wrap(RequirePermission::new(Permission::GroupManage)))
+        .route("{id}/members", web::delete().to(group::remove_member_from_group).wrap(RequirePermission::new(Permission::GroupManage)))
     );
 }
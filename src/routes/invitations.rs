@@ -0,0 +1,18 @@
+use actix_web::web;
+use crate::controllers::invitation;
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/invitations")
+        // Get Method
+        .route("", web::get().to(invitation::get_all_invitations).wrap(RequirePermission::new(Permission::UserInvite)))
+
+        // Post Method
+        .route("", web::post().to(invitation::create_invitation).wrap(RequirePermission::new(Permission::UserInvite)))
+
+        // Delete Method
+        .route("/{id}", web::delete().to(invitation::revoke_invitation).wrap(RequirePermission::new(Permission::UserInvite)))
+    );
+}
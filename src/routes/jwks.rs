@@ -0,0 +1,10 @@
+use actix_web::web;
+use crate::controllers::jwks;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/.well-known")
+        // Get Method
+        .route("/jwks.json", web::get().to(jwks::get_jwks))
+    );
+}
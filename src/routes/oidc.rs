@@ -0,0 +1,11 @@
+use actix_web::web;
+use crate::controllers::oidc;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/auth/oidc")
+        // Get Method
+        .route("/login", web::get().to(oidc::oidc_login))
+        .route("/callback", web::get().to(oidc::oidc_callback))
+    );
+}
@@ -0,0 +1,16 @@
+use actix_web::web;
+use crate::controllers::personal_token;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/tokens")
+        // Get Method
+        .route("", web::get().to(personal_token::get_my_tokens))
+
+        // Post Method
+        .route("", web::post().to(personal_token::create_my_token))
+
+        // Delete Method
+        .route("/{id}", web::delete().to(personal_token::revoke_my_token))
+    );
+}
@@ -0,0 +1,10 @@
+use actix_web::web;
+use crate::controllers::profile_picture;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/profile-pictures")
+        // Get Method
+        .route("/{user_id}/{file}", web::get().to(profile_picture::get_profile_picture))
+    );
+}
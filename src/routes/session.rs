@@ -1,5 +1,7 @@
 use actix_web::web;
 use crate::controllers::session;
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -8,3 +10,17 @@ pub fn config(cfg: &mut web::ServiceConfig) {
         .route("/refresh-token", web::post().to(session::renew_access_token))
     );
 }
+
+// Session management for the logged in user, registered under the /api scope
+pub fn protected_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/sessions")
+        // Get Method
+        .route("", web::get().to(session::get_my_sessions))
+        .route("/user/{user_id}", web::get().to(session::get_user_sessions).wrap(RequirePermission::new(Permission::SessionManage)))
+
+        // Delete Method
+        .route("/user/{user_id}", web::delete().to(session::revoke_all_user_sessions).wrap(RequirePermission::new(Permission::SessionManage)))
+        .route("/{session_id}", web::delete().to(session::revoke_my_session))
+    );
+}
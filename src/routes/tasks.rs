@@ -1,5 +1,7 @@
 use actix_web::web::{self};
 use crate::controllers::task;
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
@@ -10,13 +12,13 @@ pub fn config(cfg: &mut web::ServiceConfig) {
             .route("{id}/status", web::get().to(task::get_task_status))
             
             // Post Method
-            .route("", web::post().to(task::create_task))
+            .route("", web::post().to(task::create_task).wrap(RequirePermission::new(Permission::TaskCreate)))
             .route("/finished", web::post().to(task::create_finished_task))
 
             // Put Method
-            .route("", web::put().to(task::update_task))
+            .route("", web::put().to(task::update_task).wrap(RequirePermission::new(Permission::TaskUpdate)))
 
             // Delete Method
-            .route("{id}", web::delete().to(task::delete_task))
+            .route("{id}", web::delete().to(task::delete_task).wrap(RequirePermission::new(Permission::TaskDelete)))
     );
 }
@@ -1,21 +1,33 @@
 use actix_web::web;
-use crate::controllers::user;
+use crate::controllers::{oidc, profile_picture, two_factor, user};
+use crate::middleware::permission::RequirePermission;
+use crate::models::permission::Permission;
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/users")
             // Get Method
             .route("", web::get().to(user::get_all_users))
+            .route("/me", web::get().to(user::get_me))
             .route("{username}", web::get().to(user::get_user))
 
             // Post Method
-            .route("", web::post().to(user::create_user))
+            .route("", web::post().to(user::create_user).wrap(RequirePermission::new(Permission::UserCreate)))
+            .route("/me/password", web::post().to(user::change_my_password))
+            .route("/me/totp/enroll", web::post().to(two_factor::enroll_totp))
+            .route("/me/totp/confirm", web::post().to(two_factor::confirm_totp))
+            .route("/me/identities/oidc", web::post().to(oidc::link_oidc_identity))
+            .route("{id}/password-reset", web::post().to(user::reset_user_password).wrap(RequirePermission::new(Permission::UserResetPassword)))
+            .route("{id}/restore", web::post().to(user::restore_user).wrap(RequirePermission::new(Permission::UserRestore)))
+            .route("{username}/unlock", web::post().to(user::unlock_user).wrap(RequirePermission::new(Permission::UserUnlock)))
+            .route("{username}/upload-profile-picture", web::post().to(profile_picture::upload_profile_picture))
 
             // Put Method
             .route("", web::put().to(user::update_data_user))
-            // .route("{username}/upload-profile-picture", web::post().to(user::upload_profile_picture))
+            .route("{id}/role", web::put().to(user::change_user_role).wrap(RequirePermission::new(Permission::UserChangeRole)))
             
             // Delete Method
-            .route("{username}", web::delete().to(user::delete_user))
+            .route("/me/totp", web::delete().to(two_factor::disable_totp))
+            .route("{username}", web::delete().to(user::delete_user).wrap(RequirePermission::new(Permission::UserDelete)))
     );
 }
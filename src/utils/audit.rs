@@ -0,0 +1,29 @@
+use sqlx::{MySql, Transaction};
+
+pub const ROLE_CHANGE: &str = "user.role_change";
+pub const USER_DEACTIVATE: &str = "user.deactivate";
+pub const USER_RESTORE: &str = "user.restore";
+
+// Written inside the caller's transaction so the audit row exists only if the change does
+pub async fn record_audit(
+    tx: &mut Transaction<'_, MySql>,
+    actor_id: i32,
+    target_user_id: i32,
+    action: &str,
+    old_value: Option<&str>,
+    new_value: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let query = r"INSERT INTO audit_log (actor_id, target_user_id, action, old_value, new_value)
+                  VALUES (?, ?, ?, ?, ?)";
+
+    sqlx::query(query)
+        .bind(actor_id)
+        .bind(target_user_id)
+        .bind(action)
+        .bind(old_value)
+        .bind(new_value)
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+// Full sweeps for expired entries run once per this many inserts, not on every lookup
+const SWEEP_EVERY_INSERTS: usize = 1024;
+
+struct Entries<K, V> {
+    values: HashMap<K, (V, Instant)>,
+    inserts_since_sweep: usize,
+}
+
+// In-memory cache whose entries are trusted for a fixed time after they were stored
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<Entries<K, V>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(Entries {
+                values: HashMap::new(),
+                inserts_since_sweep: 0,
+            }),
+        }
+    }
+
+    // A stale entry is dropped when it is looked up
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+
+        match entries.values.get(key) {
+            Some((value, stored_at)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.values.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.inserts_since_sweep += 1;
+
+        if entries.inserts_since_sweep >= SWEEP_EVERY_INSERTS {
+            let ttl = self.ttl;
+            entries.values.retain(|_, (_, stored_at)| stored_at.elapsed() < ttl);
+            entries.inserts_since_sweep = 0;
+        }
+
+        entries.values.insert(key, (value, Instant::now()));
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().unwrap().values.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_fresh_entries_and_drops_stale_ones() {
+        let fresh = TtlCache::new(Duration::from_secs(60));
+        fresh.insert(1, "a");
+        assert_eq!(fresh.get(&1), Some("a"));
+
+        fresh.remove(&1);
+        assert_eq!(fresh.get(&1), None);
+
+        let stale = TtlCache::new(Duration::ZERO);
+        stale.insert(1, "a");
+        assert_eq!(stale.get(&1), None);
+        assert!(stale.entries.lock().unwrap().values.is_empty());
+    }
+
+    #[test]
+    fn sweeps_expired_entries_every_n_inserts() {
+        let cache = TtlCache::new(Duration::ZERO);
+
+        for key in 0..SWEEP_EVERY_INSERTS {
+            cache.insert(key, ());
+        }
+
+        assert_eq!(cache.entries.lock().unwrap().values.len(), 1);
+    }
+}
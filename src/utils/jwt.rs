@@ -1,108 +1,397 @@
 use std::{
-    env,
+    sync::LazyLock,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use actix_web::{HttpRequest, HttpResponse, web};
-use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use actix_web::{
+    HttpMessage, HttpRequest, HttpResponse,
+    http::{Method, header},
+    web,
+};
+use jsonwebtoken::{Header, Validation, decode, decode_header, encode, errors::ErrorKind};
 use nanoid::nanoid;
-use sqlx::{MySqlPool, Row};
+use serde::{Serialize, de::DeserializeOwned};
+use sqlx::MySqlPool;
+use uuid::Uuid;
 
 use crate::{
-    controllers::session::get_or_create_refresh_token,
+    config::{
+        jwt::{jwt_keys, token_settings},
+        origin::is_allowed_origin,
+        totp::is_totp_required,
+    },
+    controllers::session::{create_refresh_token, is_session_revoked},
     models::{
-        auth::{Claims, Token},
+        auth::{ChallengeClaims, Claims, Token},
         message::ErrorMessage,
+        session::ClientInfo,
         users::Role,
     },
 };
 
-use super::responder::ApiResponder;
+use super::{
+    cache::TtlCache,
+    responder::ApiResponder,
+    token_version::{CACHE_TTL, current_token_version},
+};
+
+const TOTP_CHALLENGE_PURPOSE: &str = "totp";
+
+// Deny-list lookups by jti. Revocations made by this instance are known at once, other
+// instances see them after at most the token version cache TTL.
+static REVOCATION_CACHE: LazyLock<TtlCache<String, bool>> =
+    LazyLock::new(|| TtlCache::new(*CACHE_TTL));
 
 pub async fn create_token(
     pool: &web::Data<MySqlPool>,
-    secret: &String,
     username: &String,
+    client: &ClientInfo,
 ) -> Result<Token, Box<dyn std::error::Error>> {
-    let expiration = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 1 * 60 * 60; // 1 hours in seconds
-    let query = r"SELECT id, role FROM users WHERE username = ?";
+    let query = r"SELECT id, username, role, must_change_password, totp_enabled, token_version
+                  FROM users WHERE username = ? AND is_active = TRUE";
 
-    let result = sqlx::query(query)
-        .bind(&username)
+    let result = sqlx::query_as::<_, TokenSubject>(query)
+        .bind(username)
         .fetch_optional(pool.get_ref())
         .await;
 
-    let user_role: Role;
-    let mut user_id: i32 = 0;
-
-    match result {
-        Ok(Some(row)) => {
-            let role_str: String = row.get("role");
-            user_id = row.get("id");
-
-            user_role = match role_str.as_str() {
-                "Anggota" => Role::Anggota,
-                "Ketua" => Role::Ketua,
-                "Sekretaris" => Role::Sekretaris,
-                _ => Role::Anggota,
-            };
-        }
-        Ok(None) => {
-            user_role = Role::Anggota;
-        }
+    // Only called after the password was verified, an unknown or deactivated username is an error
+    let subject = match result {
+        Ok(Some(subject)) => subject,
+        Ok(None) => return Err(Box::new(sqlx::Error::RowNotFound)),
         Err(e) => {
             return Err(Box::new(e));
         }
     };
 
+    let session_id = nanoid!(20);
+
+    let access_token = encode_access_token(&subject, &session_id)?;
+    let refresh_token = create_refresh_token(pool, subject.id, session_id, client).await?;
+
+    Ok(Token {
+        access_token,
+        refresh_token,
+        must_change_password: subject.must_change_password,
+    })
+}
+
+// Issue a new access token for an existing session
+pub async fn create_access_token(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+    session_id: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let query = r"SELECT id, username, role, must_change_password, totp_enabled, token_version
+                  FROM users WHERE id = ? AND is_active = TRUE";
+
+    let subject = sqlx::query_as::<_, TokenSubject>(query)
+        .bind(user_id)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    encode_access_token(&subject, session_id)
+}
+
+#[derive(sqlx::FromRow)]
+struct TokenSubject {
+    id: i32,
+    username: String,
+    role: String,
+    must_change_password: bool,
+    totp_enabled: bool,
+    token_version: i32,
+}
+
+fn encode_access_token(
+    subject: &TokenSubject,
+    session_id: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let settings = token_settings();
+    let issued_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let expiration = issued_at + settings.access_token_ttl_secs;
+
+    let role = parse_role(&subject.role);
+
     let claims = Claims {
+        sub: subject.username.clone(),
+        user_id: subject.id,
+        must_enroll_totp: is_totp_required(&role) && !subject.totp_enabled,
+        role,
+        sid: session_id.to_owned(),
+        must_change_password: subject.must_change_password,
+        scopes: None,
+        ver: subject.token_version,
+        iss: settings.issuer.clone(),
+        aud: settings.audience.clone(),
+        iat: issued_at as usize,
+        jti: Uuid::new_v4().to_string(),
+        exp: expiration as usize,
+    };
+
+    Ok(sign_token(&claims)?)
+}
+
+pub fn create_challenge_token(
+    user_id: i32,
+    username: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let expiration = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + 5 * 60; // 5 minutes in seconds
+
+    let claims = ChallengeClaims {
         sub: username.to_owned(),
         user_id,
-        role: user_role,
+        purpose: TOTP_CHALLENGE_PURPOSE.to_string(),
+        iss: token_settings().issuer.clone(),
+        aud: token_settings().audience.clone(),
+        jti: Uuid::new_v4().to_string(),
         exp: expiration as usize,
     };
 
-    let header = Header::default();
-    let encoding_key = EncodingKey::from_secret(secret.as_bytes());
-    let session_id = nanoid!(20);
+    Ok(sign_token(&claims)?)
+}
 
-    let access_token = encode(&header, &claims, &encoding_key)?;
-    let refresh_token = get_or_create_refresh_token(&pool, user_id, session_id).await?;
+// Signed with the active key, its kid tells verifiers which key to use
+fn sign_token<T: Serialize>(claims: &T) -> jsonwebtoken::errors::Result<String> {
+    let signing = &jwt_keys().signing;
 
-    Ok(Token {
-        access_token,
-        refresh_token,
-    })
+    let mut header = Header::new(signing.algorithm);
+    header.kid = Some(signing.kid.clone());
+
+    encode(&header, claims, &signing.encoding_key)
+}
+
+// Verified with the key named by the kid header, so tokens signed before a rotation stay valid
+pub fn verify_token<T: DeserializeOwned>(token: &str) -> jsonwebtoken::errors::Result<T> {
+    let header = decode_header(token)?;
+
+    let key = jwt_keys()
+        .find(header.kid.as_deref())
+        .ok_or(ErrorKind::InvalidToken)?;
+
+    let settings = token_settings();
+
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_issuer(&[&settings.issuer]);
+    validation.set_audience(&[&settings.audience]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+    Ok(decode::<T>(token, &key.decoding_key, &validation)?.claims)
+}
+
+// Deny-list an access token until it expires, e.g. on logout
+pub async fn revoke_access_token(
+    pool: &web::Data<MySqlPool>,
+    claims: &Claims,
+) -> Result<(), sqlx::Error> {
+    let now = chrono::Utc::now();
+
+    sqlx::query(r"DELETE FROM revoked_access_tokens WHERE expires_at < ?")
+        .bind(now.naive_utc())
+        .execute(pool.get_ref())
+        .await?;
+
+    let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or(now);
+
+    sqlx::query(r"INSERT IGNORE INTO revoked_access_tokens (jti, expires_at) VALUES (?, ?)")
+        .bind(&claims.jti)
+        .bind(expires_at.naive_utc())
+        .execute(pool.get_ref())
+        .await?;
+
+    REVOCATION_CACHE.insert(claims.jti.clone(), true);
+
+    Ok(())
+}
+
+pub async fn is_access_token_revoked(
+    pool: &web::Data<MySqlPool>,
+    jti: &str,
+) -> Result<bool, sqlx::Error> {
+    let jti = jti.to_string();
+
+    if let Some(revoked) = REVOCATION_CACHE.get(&jti) {
+        return Ok(revoked);
+    }
+
+    let query = r"SELECT jti FROM revoked_access_tokens WHERE jti = ?";
+
+    let revoked = sqlx::query_as::<_, (String,)>(query)
+        .bind(&jti)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .is_some();
+
+    REVOCATION_CACHE.insert(jti, revoked);
+
+    Ok(revoked)
 }
 
-pub fn decode_token(token: &str) -> Result<Claims, String> {
-    let decoding_key = DecodingKey::from_secret(env::var("SECRET_KEY").unwrap().as_bytes());
+// Marks a challenge token as used, false when it was used before. Its jti goes into the
+// access token deny-list, which already expires rows by their exp.
+pub async fn consume_challenge_token(
+    pool: &web::Data<MySqlPool>,
+    claims: &ChallengeClaims,
+) -> Result<bool, sqlx::Error> {
+    let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0)
+        .unwrap_or_else(chrono::Utc::now);
+
+    let result =
+        sqlx::query(r"INSERT IGNORE INTO revoked_access_tokens (jti, expires_at) VALUES (?, ?)")
+            .bind(&claims.jti)
+            .bind(expires_at.naive_utc())
+            .execute(pool.get_ref())
+            .await?;
+
+    REVOCATION_CACHE.insert(claims.jti.clone(), true);
 
-    match decode::<Claims>(&token, &decoding_key, &Validation::default()) {
-        Ok(token_data) => Ok(token_data.claims),
-        Err(e) => Err(ErrorMessage::TokenDecodeError {
+    Ok(result.rows_affected() == 1)
+}
+
+pub fn decode_challenge_token(token: &str) -> Result<ChallengeClaims, String> {
+    let claims = verify_token::<ChallengeClaims>(token).map_err(|e| {
+        ErrorMessage::TokenDecodeError {
             details: e.to_string(),
         }
-        .to_string()),
+        .to_string()
+    })?;
+
+    if claims.purpose != TOTP_CHALLENGE_PURPOSE {
+        return Err(ErrorMessage::TokenInvalid.to_string());
     }
+
+    Ok(claims)
 }
 
-pub async fn extract_claims(req: &HttpRequest) -> Result<Claims, HttpResponse> {
-    let auth_header = req.headers().get("Authorization").ok_or_else(|| {
-        ApiResponder::unauthorized(ErrorMessage::NoAuthHeader.to_string(), None::<()>)
-    })?;
+pub fn parse_role(role: &str) -> Role {
+    match role {
+        "Anggota" => Role::Anggota,
+        "Ketua" => Role::Ketua,
+        "Sekretaris" => Role::Sekretaris,
+        _ => Role::Anggota,
+    }
+}
+
+// Claims of a valid access token, also rejected once revoked by logout, a token_version bump
+// or the revocation of its session
+pub async fn decode_token(pool: &web::Data<MySqlPool>, token: &str) -> Result<Claims, HttpResponse> {
+    let claims = verify_token::<Claims>(token).map_err(|e| {
+        let message = match e.kind() {
+            ErrorKind::ExpiredSignature => "Token expired".to_string(),
+            ErrorKind::InvalidToken => "Invalid token".to_string(),
+            _ => "Token validation error".to_string(),
+        };
 
-    let auth_str = auth_header.to_str().map_err(|_| {
-        ApiResponder::unauthorized(ErrorMessage::InvalidAuthHeader.to_string(), None::<()>)
+        ApiResponder::unauthorized(message, None::<()>)
     })?;
 
-    if !auth_str.starts_with("Bearer ") {
-        return Err(ApiResponder::unauthorized(
-            ErrorMessage::InvalidAuthScheme.to_string(),
+    match current_token_version(pool, claims.user_id).await {
+        Ok(Some(version)) if version == claims.ver => {}
+        Ok(_) => {
+            return Err(ApiResponder::unauthorized(
+                ErrorMessage::TokenOutdated.to_string(),
+                None::<()>,
+            ));
+        }
+        Err(e) => return Err(ApiResponder::<()>::handle_error(e)),
+    }
+
+    match is_session_revoked(pool, &claims.sid).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return Err(ApiResponder::unauthorized(
+                ErrorMessage::TokenInvalid.to_string(),
+                None::<()>,
+            ));
+        }
+        Err(e) => return Err(ApiResponder::<()>::handle_error(e)),
+    }
+
+    match is_access_token_revoked(pool, &claims.jti).await {
+        Ok(false) => Ok(claims),
+        Ok(true) => Err(ApiResponder::unauthorized(
+            ErrorMessage::TokenInvalid.to_string(),
             None::<()>,
-        ));
+        )),
+        Err(e) => Err(ApiResponder::<()>::handle_error(e)),
+    }
+}
+
+// Claims verified by AuthMiddleware, only available inside the /api scope
+pub fn extract_claims(req: &HttpRequest) -> Result<Claims, HttpResponse> {
+    req.extensions().get::<Claims>().cloned().ok_or_else(|| {
+        ApiResponder::unauthorized(ErrorMessage::UnAuthorized.to_string(), None::<()>)
+    })
+}
+
+// Claims of the caller outside the /api scope, checked the same way AuthMiddleware does
+pub async fn request_claims(
+    pool: &web::Data<MySqlPool>,
+    req: &HttpRequest,
+) -> Result<Claims, HttpResponse> {
+    if let Ok(claims) = extract_claims(req) {
+        return Ok(claims);
+    }
+
+    let token = read_access_token(req)?;
+    decode_token(pool, &token).await
+}
+
+// Access token from the Authorization header, or from the access_token cookie for browsers.
+// Cookies are sent automatically, so unsafe methods must come from an allowed origin.
+pub fn read_access_token(req: &HttpRequest) -> Result<String, HttpResponse> {
+    if let Some(auth_header) = req.headers().get(header::AUTHORIZATION) {
+        let auth_str = auth_header.to_str().map_err(|_| {
+            ApiResponder::unauthorized(ErrorMessage::InvalidAuthHeader.to_string(), None::<()>)
+        })?;
+
+        let token = auth_str.strip_prefix("Bearer ").ok_or_else(|| {
+            ApiResponder::unauthorized(ErrorMessage::InvalidAuthScheme.to_string(), None::<()>)
+        })?;
+
+        return Ok(token.to_string());
+    }
+
+    if let Some(cookie) = req.cookie("access_token")
+        && !cookie.value().is_empty()
+    {
+        if !is_trusted_origin(req) {
+            return Err(ApiResponder::forbidden(
+                ErrorMessage::CsrfCheckFailed.to_string(),
+                None::<()>,
+            ));
+        }
+
+        return Ok(cookie.value().to_string());
+    }
+
+    Err(ApiResponder::unauthorized(
+        ErrorMessage::NoAuthHeader.to_string(),
+        None::<()>,
+    ))
+}
+
+fn is_trusted_origin(req: &HttpRequest) -> bool {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return true;
+    }
+
+    let headers = req.headers();
+
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        return origin.to_str().map(is_allowed_origin).unwrap_or(false);
     }
 
-    let token = &auth_str[7..];
-    decode_token(token).map_err(|e| ApiResponder::unauthorized(e, None::<()>))
+    // Some browsers omit Origin on same-origin requests, fall back to the Referer origin
+    headers
+        .get(header::REFERER)
+        .and_then(|referer| referer.to_str().ok())
+        .and_then(|referer| {
+            let scheme_end = referer.find("://")? + 3;
+            let path_start = referer[scheme_end..]
+                .find('/')
+                .map_or(referer.len(), |i| scheme_end + i);
+            Some(is_allowed_origin(&referer[..path_start]))
+        })
+        .unwrap_or(false)
 }
@@ -1,3 +1,13 @@
 pub mod responder;
 pub mod security;
-pub mod jwt;
\ No newline at end of file
+pub mod jwt;
+pub mod notifier;
+pub mod throttle;
+pub mod totp;
+pub mod token_version;
+pub mod audit;
+pub mod oidc;
+pub mod password_policy;
+pub mod storage;
+pub mod profile_picture;
+pub mod cache;
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use crate::models::password_reset::PasswordResetNotice;
+
+// Delivers messages to users, swap the implementation with the NOTIFIER env variable
+pub trait Notifier: Send + Sync {
+    fn send_password_reset(&self, notice: &PasswordResetNotice) -> io::Result<()>;
+}
+
+// Writes notices to the application log, reset tokens end up in plain text so it must be
+// chosen explicitly with NOTIFIER=log and is only meant for local development
+pub struct LogNotifier;
+
+impl Notifier for LogNotifier {
+    fn send_password_reset(&self, notice: &PasswordResetNotice) -> io::Result<()> {
+        tracing::info!(
+            username = %notice.username,
+            expires_at = %notice.expires_at,
+            "Password reset token: {}",
+            notice.token
+        );
+        Ok(())
+    }
+}
+
+// Appends notices as JSON lines to a file
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileNotifier { path: path.into() }
+    }
+}
+
+impl Notifier for FileNotifier {
+    fn send_password_reset(&self, notice: &PasswordResetNotice) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let line = serde_json::json!({
+            "type": "password_reset",
+            "username": notice.username,
+            "token": notice.token,
+            "expires_at": notice.expires_at,
+        });
+
+        writeln!(file, "{}", line)
+    }
+}
+
+// None when NOTIFIER is unset, password reset is then turned off
+pub fn notifier_from_env() -> Option<Arc<dyn Notifier>> {
+    match env::var("NOTIFIER").as_deref() {
+        Ok("file") => Some(Arc::new(FileNotifier::new(
+            env::var("NOTIFIER_FILE_PATH").unwrap_or_else(|_| "notifications.log".to_string()),
+        ))),
+        Ok("log") => {
+            tracing::warn!("NOTIFIER=log writes password reset tokens to the application log");
+            Some(Arc::new(LogNotifier))
+        }
+        Ok(other) => panic!("Unsupported NOTIFIER {}, use file or log", other),
+        Err(_) => {
+            tracing::warn!("NOTIFIER is not set, password reset is disabled");
+            None
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::error::Error;
+
+use openidconnect::{
+    AuthorizationCode, ClientId, ClientSecret, CsrfToken, IssuerUrl, Nonce, PkceCodeChallenge,
+    PkceCodeVerifier, RedirectUrl, Scope, TokenResponse,
+    core::{CoreAuthenticationFlow, CoreClient, CoreProviderMetadata},
+    reqwest,
+};
+
+use crate::config::oidc::OidcSettings;
+
+// Kept server side until the callback, only the state travels through the browser
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+}
+
+pub struct OidcIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+}
+
+fn http_client() -> Result<reqwest::Client, reqwest::Error> {
+    // Following redirects would expose the client to SSRF through the provider
+    reqwest::ClientBuilder::new()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+}
+
+async fn discover(
+    settings: &OidcSettings,
+    http_client: &reqwest::Client,
+) -> Result<CoreProviderMetadata, Box<dyn Error>> {
+    let issuer_url = IssuerUrl::new(settings.issuer_url.clone())?;
+
+    Ok(CoreProviderMetadata::discover_async(issuer_url, http_client).await?)
+}
+
+// Authorization code flow with PKCE (S256) and a nonce bound to the ID token
+pub async fn authorization_request(
+    settings: &OidcSettings,
+) -> Result<AuthorizationRequest, Box<dyn Error>> {
+    let http_client = http_client()?;
+
+    let client = CoreClient::from_provider_metadata(
+        discover(settings, &http_client).await?,
+        ClientId::new(settings.client_id.clone()),
+        settings.client_secret.clone().map(ClientSecret::new),
+    )
+    .set_redirect_uri(RedirectUrl::new(settings.redirect_url.clone())?);
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    let mut request = client
+        .authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .set_pkce_challenge(pkce_challenge);
+
+    for scope in &settings.scopes {
+        request = request.add_scope(Scope::new(scope.clone()));
+    }
+
+    let (url, state, nonce) = request.url();
+
+    Ok(AuthorizationRequest {
+        url: url.to_string(),
+        state: state.secret().clone(),
+        nonce: nonce.secret().clone(),
+        pkce_verifier: pkce_verifier.secret().clone(),
+    })
+}
+
+// Redeem the code and verify the ID token signature, issuer, audience, expiry and nonce
+pub async fn exchange_code(
+    settings: &OidcSettings,
+    code: &str,
+    pkce_verifier: &str,
+    nonce: &str,
+) -> Result<OidcIdentity, Box<dyn Error>> {
+    let http_client = http_client()?;
+
+    let client = CoreClient::from_provider_metadata(
+        discover(settings, &http_client).await?,
+        ClientId::new(settings.client_id.clone()),
+        settings.client_secret.clone().map(ClientSecret::new),
+    )
+    .set_redirect_uri(RedirectUrl::new(settings.redirect_url.clone())?);
+
+    let token_response = client
+        .exchange_code(AuthorizationCode::new(code.to_string()))?
+        .set_pkce_verifier(PkceCodeVerifier::new(pkce_verifier.to_string()))
+        .request_async(&http_client)
+        .await?;
+
+    let id_token = token_response
+        .id_token()
+        .ok_or("Identity provider did not return an ID token")?;
+
+    let claims = id_token.claims(&client.id_token_verifier(), &Nonce::new(nonce.to_string()))?;
+
+    Ok(OidcIdentity {
+        issuer: claims.issuer().to_string(),
+        subject: claims.subject().to_string(),
+        email: claims.email().map(|email| email.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use argon2::password_hash::rand_core::OsRng;
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+    use rsa::{RsaPrivateKey, pkcs1::EncodeRsaPrivateKey, traits::PublicKeyParts};
+    use serde_json::json;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{body_string_contains, method, path},
+    };
+
+    use super::*;
+
+    const CLIENT_ID: &str = "task-management";
+
+    // Local OpenID provider serving discovery, JWKS and a token endpoint
+    async fn mock_provider(key: &RsaPrivateKey) -> MockServer {
+        let server = MockServer::start().await;
+        let issuer = server.uri();
+
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", issuer),
+                "token_endpoint": format!("{}/token", issuer),
+                "jwks_uri": format!("{}/jwks", issuer),
+                "response_types_supported": ["code"],
+                "subject_types_supported": ["public"],
+                "id_token_signing_alg_values_supported": ["RS256"],
+            })))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/jwks"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "keys": [{
+                    "kty": "RSA",
+                    "use": "sig",
+                    "alg": "RS256",
+                    "kid": "mock",
+                    "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                    "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+                }]
+            })))
+            .mount(&server)
+            .await;
+
+        server
+    }
+
+    fn settings(issuer: &str) -> OidcSettings {
+        OidcSettings {
+            issuer_url: issuer.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_url: "http://localhost:8080/auth/oidc/callback".to_string(),
+            scopes: vec!["email".to_string(), "profile".to_string()],
+        }
+    }
+
+    fn id_token(key: &RsaPrivateKey, issuer: &str, nonce: &str) -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("mock".to_string());
+
+        let der = key.to_pkcs1_der().unwrap();
+
+        encode(
+            &header,
+            &json!({
+                "iss": issuer,
+                "sub": "student-42",
+                "aud": CLIENT_ID,
+                "iat": now,
+                "exp": now + 300,
+                "nonce": nonce,
+                "email": "student42@campus.test",
+                "preferred_username": "student42",
+            }),
+            &EncodingKey::from_rsa_der(der.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn login_flow_against_mock_provider() {
+        let key = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        let server = mock_provider(&key).await;
+        let settings = settings(&server.uri());
+
+        let request = authorization_request(&settings).await.unwrap();
+        assert!(request.url.starts_with(&format!("{}/authorize?", server.uri())));
+        assert!(request.url.contains("code_challenge_method=S256"));
+        assert!(request.url.contains(&format!("state={}", request.state)));
+
+        // The provider only answers when the PKCE verifier is sent with the code
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("code=valid-code"))
+            .and(body_string_contains(format!(
+                "code_verifier={}",
+                request.pkce_verifier
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "provider-access-token",
+                "token_type": "Bearer",
+                "expires_in": 300,
+                "id_token": id_token(&key, &server.uri(), &request.nonce),
+            })))
+            .mount(&server)
+            .await;
+
+        let identity = exchange_code(&settings, "valid-code", &request.pkce_verifier, &request.nonce)
+            .await
+            .unwrap();
+
+        assert_eq!(identity.issuer, server.uri());
+        assert_eq!(identity.subject, "student-42");
+        assert_eq!(identity.email.as_deref(), Some("student42@campus.test"));
+
+        // An ID token minted for another login attempt is rejected
+        assert!(
+            exchange_code(&settings, "valid-code", &request.pkce_verifier, "other-nonce")
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use serde::Serialize;
+
+use crate::config::password::{PasswordPolicy, password_policy};
+use crate::models::message::ErrorMessage;
+
+// Bundled list of passwords that show up in every leaked credential dump
+static COMMON_PASSWORDS: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
+    include_str!("common_passwords.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+});
+
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl PasswordPolicy {
+    // Every rule the password breaks, empty when it is acceptable
+    pub fn check(&self, field: &'static str, password: &str, username: &str) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        let lowered = password.to_lowercase();
+
+        if password.chars().count() < self.min_length {
+            errors.push(ErrorMessage::PasswordTooShort {
+                min_length: self.min_length,
+            });
+        }
+
+        let username = username.trim().to_lowercase();
+
+        if self.reject_username && !username.is_empty() && lowered.contains(&username) {
+            errors.push(ErrorMessage::PasswordContainsUsername);
+        }
+
+        if self.reject_common && COMMON_PASSWORDS.contains(lowered.as_str()) {
+            errors.push(ErrorMessage::PasswordTooCommon);
+        }
+
+        errors
+            .into_iter()
+            .map(|message| FieldError {
+                field,
+                message: message.to_string(),
+            })
+            .collect()
+    }
+}
+
+// Check a password against the configured policy
+pub fn validate_password(field: &'static str, password: &str, username: &str) -> Vec<FieldError> {
+    password_policy().check(field, password, username)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: PasswordPolicy = PasswordPolicy {
+        min_length: 8,
+        reject_username: true,
+        reject_common: true,
+    };
+
+    #[test]
+    fn accepts_strong_password() {
+        assert!(POLICY.check("password", "correct-horse-battery", "budi").is_empty());
+    }
+
+    #[test]
+    fn reports_every_broken_rule() {
+        let messages: Vec<String> = POLICY
+            .check("password", "", "budi")
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(messages, vec![ErrorMessage::PasswordTooShort { min_length: 8 }.to_string()]);
+
+        let errors = POLICY.check("new_password", "Budi2024!", "BUDI");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "new_password");
+        assert_eq!(errors[0].message, ErrorMessage::PasswordContainsUsername.to_string());
+
+        let errors = POLICY.check("password", "Password123", "budi");
+        assert_eq!(errors[0].message, ErrorMessage::PasswordTooCommon.to_string());
+    }
+}
@@ -0,0 +1,108 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageReader, Limits, imageops::FilterType};
+
+use crate::config::profile_picture::profile_picture_settings;
+
+// Square sizes generated for every upload, the first one is the main picture
+pub const PICTURE_SIZES: &[u32] = &[256, 64];
+
+pub enum ProfilePictureError {
+    Unsupported,
+    Invalid(String),
+}
+
+// Storage key of one size of a picture, picture ids are unique so the files never change
+pub fn picture_key(user_id: i32, picture_id: &str, size: u32) -> String {
+    format!("profile-pictures/{}/{}_{}.png", user_id, picture_id, size)
+}
+
+// Public URL of a stored picture, served by the profile_picture controller
+pub fn picture_url(key: &str) -> String {
+    format!("/{}", key)
+}
+
+// Picture id of a URL made by picture_url for this user, None for anything else
+pub fn picture_id_from_url(user_id: i32, url: &str) -> Option<String> {
+    let file = url.strip_prefix(&format!("/profile-pictures/{}/", user_id))?;
+    let (picture_id, _) = file.strip_suffix(".png")?.rsplit_once('_')?;
+
+    Some(picture_id.to_string())
+}
+
+// Decode a PNG, JPEG or WebP upload and encode a square PNG for every size
+pub fn resize_picture(bytes: &[u8]) -> Result<Vec<(u32, Vec<u8>)>, ProfilePictureError> {
+    let settings = profile_picture_settings();
+
+    // The format is sniffed from the content, the client supplied content type is not trusted
+    let mut reader = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|e| ProfilePictureError::Invalid(e.to_string()))?;
+
+    match reader.format() {
+        Some(ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP) => {}
+        _ => return Err(ProfilePictureError::Unsupported),
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(settings.max_dimension);
+    limits.max_image_height = Some(settings.max_dimension);
+    reader.limits(limits);
+
+    let picture = reader
+        .decode()
+        .map_err(|e| ProfilePictureError::Invalid(e.to_string()))?;
+
+    PICTURE_SIZES
+        .iter()
+        .map(|&size| {
+            let mut encoded = Cursor::new(Vec::new());
+
+            picture
+                .resize_to_fill(size, size, FilterType::Lanczos3)
+                .write_to(&mut encoded, ImageFormat::Png)
+                .map_err(|e| ProfilePictureError::Invalid(e.to_string()))?;
+
+            Ok((size, encoded.into_inner()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+
+    #[test]
+    fn resizes_into_every_square_size() {
+        let mut upload = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::new(320, 200))
+            .write_to(&mut upload, ImageFormat::Jpeg)
+            .unwrap();
+
+        let resized = resize_picture(upload.get_ref()).ok().unwrap();
+        assert_eq!(resized.len(), PICTURE_SIZES.len());
+
+        for (size, png) in resized {
+            let decoded = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (size, size));
+        }
+    }
+
+    #[test]
+    fn rejects_non_images() {
+        assert!(matches!(
+            resize_picture(b"GIF89a not allowed"),
+            Err(ProfilePictureError::Unsupported)
+        ));
+        assert!(matches!(resize_picture(b"plain text"), Err(ProfilePictureError::Unsupported)));
+    }
+
+    #[test]
+    fn finds_picture_id_only_in_own_urls() {
+        let url = picture_url(&picture_key(7, "a_b-c", 256));
+        assert_eq!(picture_id_from_url(7, &url).as_deref(), Some("a_b-c"));
+        assert_eq!(picture_id_from_url(8, &url), None);
+        assert_eq!(picture_id_from_url(7, "https://example.com/me.png"), None);
+    }
+}
@@ -58,6 +58,17 @@ impl<T> ApiResponder<T> {
         })
     }
 
+    pub fn forbidden(message: String, data: Option<T>) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::Forbidden().json(ApiResponder {
+            status: Status::Forbidden.into(),
+            message,
+            data,
+        })
+    }
+
     pub fn conflict(message: String, data: Option<T>) -> HttpResponse
     where
         T: Serialize,
@@ -102,6 +113,52 @@ impl<T> ApiResponder<T> {
         })
     }
 
+    pub fn payload_too_large(message: String, data: Option<T>) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::PayloadTooLarge().json(ApiResponder {
+            status: Status::PayloadTooLarge.into(),
+            message,
+            data,
+        })
+    }
+
+    pub fn unsupported_media_type(message: String, data: Option<T>) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::UnsupportedMediaType().json(ApiResponder {
+            status: Status::UnsupportedMediaType.into(),
+            message,
+            data,
+        })
+    }
+
+    pub fn service_unavailable(message: String, data: Option<T>) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::ServiceUnavailable().json(ApiResponder {
+            status: Status::ServiceUnavailable.into(),
+            message,
+            data,
+        })
+    }
+
+    pub fn too_many_requests(message: String, data: Option<T>, retry_after_secs: i64) -> HttpResponse
+    where
+        T: Serialize,
+    {
+        HttpResponse::TooManyRequests()
+            .insert_header(("Retry-After", retry_after_secs.to_string()))
+            .json(ApiResponder {
+                status: Status::TooManyRequests.into(),
+                message,
+                data,
+            })
+    }
+
     pub fn success_with_cookie(
         message: String,
         data: Option<T>,
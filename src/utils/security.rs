@@ -1,28 +1,162 @@
+use std::{sync::LazyLock, time::Duration};
+
 use actix_web::web;
-use argon2::password_hash::{SaltString, rand_core::OsRng};
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::{self, SaltString, rand_core::OsRng};
+use argon2::{Algorithm, Argon2, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use sha2::{Digest, Sha256};
 use sqlx::{MySqlPool, Row};
 
-pub fn hash_password(password: &str) -> String {
+use crate::config::password::argon2_params;
+
+use super::cache::TtlCache;
+
+// Verified against when the username does not exist, so unknown users cost the same Argon2 work
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password("dummy-password").unwrap_or_else(|e| panic!("Failed to hash dummy password: {}", e))
+});
+
+// Upgraded hashes from the password step of a TOTP login, stored once the code is verified.
+// Kept as long as the challenge token is valid, as (verified hash, new hash).
+static DEFERRED_REHASHES: LazyLock<TtlCache<String, (String, String)>> =
+    LazyLock::new(|| TtlCache::new(Duration::from_secs(5 * 60)));
+
+// Compute the dummy hash at startup instead of during the first unknown-user login
+pub fn init_dummy_hash() {
+    LazyLock::force(&DUMMY_HASH);
+}
+
+fn argon2() -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params().clone())
+}
+
+pub fn hash_password(password: &str) -> Result<String, password_hash::Error> {
     let salt = SaltString::generate(&mut OsRng);
-    let argon2 = Argon2::default();
 
-    match argon2.hash_password(password.as_bytes(), &salt) {
-        Ok(hashed) => hashed.to_string(),
-        Err(e) => e.to_string(),
+    Ok(argon2().hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+// True when the stored hash was made with another algorithm or cost than configured now
+fn needs_rehash(hashed_password: &PasswordHash) -> bool {
+    if hashed_password.algorithm != Algorithm::Argon2id.ident()
+        || hashed_password.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    let current = argon2_params();
+
+    match argon2::Params::try_from(hashed_password) {
+        Ok(params) => {
+            params.m_cost() != current.m_cost()
+                || params.t_cost() != current.t_cost()
+                || params.p_cost() != current.p_cost()
+        }
+        Err(_) => true,
     }
 }
 
+// The stored hash and its replacement, None when the stored hash is up to date
+async fn outdated_hash(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+    password: &str,
+) -> Result<Option<(String, String)>, sqlx::Error> {
+    let stored = sqlx::query_scalar::<_, String>(r"SELECT password FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let Some(stored) = stored else {
+        return Ok(None);
+    };
+
+    match PasswordHash::new(&stored) {
+        Ok(parsed) if !needs_rehash(&parsed) => return Ok(None),
+        _ => {}
+    }
+
+    match hash_password(password) {
+        Ok(rehashed) => Ok(Some((stored, rehashed))),
+        Err(e) => {
+            tracing::warn!("Failed to rehash password of {}: {}", username, e);
+            Ok(None)
+        }
+    }
+}
+
+// Upgrade the stored hash after a successful login, a failure here must not block the login
+pub async fn rehash_password_if_outdated(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+    password: &str,
+) -> Result<bool, sqlx::Error> {
+    match outdated_hash(pool, username, password).await? {
+        Some((stored, rehashed)) => replace_password_hash(pool, username, &stored, &rehashed).await,
+        None => Ok(false),
+    }
+}
+
+// The password is only known before the TOTP step, so the new hash waits for the code
+pub async fn defer_rehash_if_outdated(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+    password: &str,
+) -> Result<(), sqlx::Error> {
+    if let Some(hashes) = outdated_hash(pool, username, password).await? {
+        DEFERRED_REHASHES.insert(username.to_string(), hashes);
+    }
+
+    Ok(())
+}
+
+// Called once the TOTP code of the login is verified
+pub async fn apply_deferred_rehash(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+) -> Result<bool, sqlx::Error> {
+    let username = username.to_string();
+
+    let Some((stored, rehashed)) = DEFERRED_REHASHES.get(&username) else {
+        return Ok(false);
+    };
+
+    DEFERRED_REHASHES.remove(&username);
+    replace_password_hash(pool, &username, &stored, &rehashed).await
+}
+
+async fn replace_password_hash(
+    pool: &web::Data<MySqlPool>,
+    username: &str,
+    stored: &str,
+    rehashed: &str,
+) -> Result<bool, sqlx::Error> {
+    // Only replace the hash that was verified, a concurrent password change wins
+    let result = sqlx::query(r"UPDATE users SET password = ? WHERE username = ? AND password = ?")
+        .bind(rehashed)
+        .bind(username)
+        .bind(stored)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Fast hash for random single-use tokens, these are looked up by their hash
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 pub async fn verify_password(
     pool: &web::Data<MySqlPool>,
     username: &String,
     password: &String,
 ) -> bool {
-    let argon2 = Argon2::default();
-    let query = r"SELECT password FROM users WHERE username = ?";
+    let argon2 = argon2();
+    // Deactivated users fail like unknown ones
+    let query = r"SELECT password FROM users WHERE username = ? AND is_active = TRUE";
 
     let result = sqlx::query(query)
-        .bind(&username)
+        .bind(username)
         .fetch_optional(pool.get_ref())
         .await;
 
@@ -37,8 +171,58 @@ pub async fn verify_password(
                 Err(_) => false,
             }
         }
-        Ok(None) => false,
+        Ok(None) => {
+            if let Ok(parsed_password) = PasswordHash::new(&DUMMY_HASH) {
+                let _ = argon2.verify_password(password.as_bytes(), &parsed_password);
+            }
+            false
+        }
         Err(_) => false,
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Only the PHC string is inspected, the salt and output are placeholders
+    fn phc(algorithm: &str, version: u32, m_cost: u32, t_cost: u32, p_cost: u32) -> String {
+        format!(
+            "${}$v={}$m={},t={},p={}$c2FsdHNhbHRzYWx0$aGFzaGhhc2hoYXNoaGFzaGhhc2hoYXNoaGFzaA",
+            algorithm, version, m_cost, t_cost, p_cost
+        )
+    }
+
+    fn outdated(hash: &str) -> bool {
+        needs_rehash(&PasswordHash::new(hash).unwrap())
+    }
+
+    #[test]
+    fn keeps_hashes_made_with_the_current_settings() {
+        let current = argon2_params();
+        let (m, t, p) = (current.m_cost(), current.t_cost(), current.p_cost());
+
+        assert!(!outdated(&phc("argon2id", 19, m, t, p)));
+        assert!(!outdated(&hash_password("correct horse").unwrap()));
+    }
+
+    #[test]
+    fn rehashes_other_algorithms_and_versions() {
+        let current = argon2_params();
+        let (m, t, p) = (current.m_cost(), current.t_cost(), current.p_cost());
+
+        assert!(outdated(&phc("argon2i", 19, m, t, p)));
+        assert!(outdated(&phc("argon2d", 19, m, t, p)));
+        assert!(outdated(&phc("argon2id", 16, m, t, p)));
+    }
+
+    #[test]
+    fn rehashes_when_any_cost_differs() {
+        let current = argon2_params();
+        let (m, t, p) = (current.m_cost(), current.t_cost(), current.p_cost());
+
+        assert!(outdated(&phc("argon2id", 19, m * 2, t, p)));
+        assert!(outdated(&phc("argon2id", 19, m, t + 1, p)));
+        assert!(outdated(&phc("argon2id", 19, m, t, p + 1)));
+    }
+}
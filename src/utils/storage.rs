@@ -0,0 +1,71 @@
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+// Stores uploaded files by key, swap the implementation with the STORAGE env variable
+pub trait Storage: Send + Sync {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+// Keeps files below a directory on the local filesystem
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        LocalStorage { root: root.into() }
+    }
+
+    // Keys are relative paths, anything that could escape the root is refused
+    fn path_for(&self, key: &str) -> io::Result<PathBuf> {
+        let relative = Path::new(key);
+
+        if key.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid storage key"));
+        }
+
+        Ok(self.root.join(relative))
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path_for(key)?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, bytes)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path_for(key)?) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path_for(key)?) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+pub fn storage_from_env() -> Arc<dyn Storage> {
+    match env::var("STORAGE").as_deref() {
+        Ok("local") | Err(_) => Arc::new(LocalStorage::new(
+            env::var("STORAGE_LOCAL_DIR").unwrap_or_else(|_| "uploads".to_string()),
+        )),
+        Ok(other) => panic!("Unsupported STORAGE {}, use local", other),
+    }
+}
@@ -0,0 +1,158 @@
+use actix_web::web;
+use chrono::{Duration, NaiveDateTime};
+use sqlx::MySqlPool;
+
+// Failed login tracking, every failure past the free attempts doubles the wait
+pub struct ThrottlePolicy {
+    pub scope: &'static str,
+    pub free_attempts: i32,
+    pub base_delay_secs: i64,
+    pub max_delay_secs: i64,
+}
+
+pub const USERNAME_POLICY: ThrottlePolicy = ThrottlePolicy {
+    scope: "username",
+    free_attempts: 3,
+    base_delay_secs: 2,
+    max_delay_secs: 15 * 60,
+};
+
+// Higher limit, a whole class may share one campus IP
+pub const IP_POLICY: ThrottlePolicy = ThrottlePolicy {
+    scope: "ip",
+    free_attempts: 20,
+    base_delay_secs: 2,
+    max_delay_secs: 15 * 60,
+};
+
+// Failures older than this no longer count
+const RESET_WINDOW_MINUTES: i64 = 60;
+
+impl ThrottlePolicy {
+    // The first free_attempts failures never lock, the next one waits base_delay_secs
+    fn delay_for(&self, failed_count: i32) -> Option<Duration> {
+        let over = failed_count - self.free_attempts;
+
+        if over <= 0 {
+            return None;
+        }
+
+        let delay = self
+            .base_delay_secs
+            .saturating_mul(1_i64 << (over - 1).min(32))
+            .min(self.max_delay_secs);
+
+        Some(Duration::seconds(delay))
+    }
+}
+
+// Counts an attempt as failed before the credentials are checked, so a burst of parallel
+// requests can't all pass the lock check. Seconds until the identifier may try again when
+// it is locked, the attempt is then not counted.
+pub async fn reserve_login_attempt(
+    pool: &web::Data<MySqlPool>,
+    policy: &ThrottlePolicy,
+    identifier: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        r"INSERT IGNORE INTO login_attempts (scope, identifier, failed_count, last_failed_at)
+          VALUES (?, ?, 0, ?)",
+    )
+    .bind(policy.scope)
+    .bind(identifier)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+
+    // Parallel attempts for the same identifier queue on this row lock until commit
+    let (failed_count, last_failed_at, locked_until) =
+        sqlx::query_as::<_, (i32, NaiveDateTime, Option<NaiveDateTime>)>(
+            r"SELECT failed_count, last_failed_at, locked_until FROM login_attempts
+              WHERE scope = ? AND identifier = ? FOR UPDATE",
+        )
+        .bind(policy.scope)
+        .bind(identifier)
+        .fetch_one(&mut tx)
+        .await?;
+
+    if let Some(locked_until) = locked_until.filter(|locked_until| *locked_until > now) {
+        tx.commit().await?;
+        return Ok(Some((locked_until - now).num_seconds().max(1)));
+    }
+
+    let failed_count = if last_failed_at > now - Duration::minutes(RESET_WINDOW_MINUTES) {
+        failed_count + 1
+    } else {
+        1
+    };
+
+    let locked_until = policy.delay_for(failed_count).map(|delay| now + delay);
+
+    sqlx::query(
+        r"UPDATE login_attempts SET failed_count = ?, last_failed_at = ?, locked_until = ?
+          WHERE scope = ? AND identifier = ?",
+    )
+    .bind(failed_count)
+    .bind(now)
+    .bind(locked_until)
+    .bind(policy.scope)
+    .bind(identifier)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(None)
+}
+
+// Gives back a reserved attempt that succeeded without clearing earlier failures, e.g. for a
+// shared IP address
+pub async fn release_login_attempt(
+    pool: &web::Data<MySqlPool>,
+    policy: &ThrottlePolicy,
+    identifier: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r"UPDATE login_attempts SET failed_count = GREATEST(failed_count - 1, 0)
+          WHERE scope = ? AND identifier = ?",
+    )
+    .bind(policy.scope)
+    .bind(identifier)
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(())
+}
+
+pub async fn clear_failed_logins(
+    pool: &web::Data<MySqlPool>,
+    policy: &ThrottlePolicy,
+    identifier: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(r"DELETE FROM login_attempts WHERE scope = ? AND identifier = ?")
+        .bind(policy.scope)
+        .bind(identifier)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locks_only_after_the_free_attempts() {
+        for failed_count in 1..=USERNAME_POLICY.free_attempts {
+            assert!(USERNAME_POLICY.delay_for(failed_count).is_none());
+        }
+
+        assert_eq!(USERNAME_POLICY.delay_for(4), Some(Duration::seconds(2)));
+        assert_eq!(USERNAME_POLICY.delay_for(5), Some(Duration::seconds(4)));
+        assert_eq!(USERNAME_POLICY.delay_for(100), Some(Duration::seconds(15 * 60)));
+    }
+}
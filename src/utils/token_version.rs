@@ -0,0 +1,47 @@
+use std::{env, sync::LazyLock, time::Duration};
+
+use actix_web::web;
+use sqlx::MySqlPool;
+
+use super::cache::TtlCache;
+
+// How long a looked up version is trusted, other instances see a bump after at most this long
+pub static CACHE_TTL: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(
+        env::var("TOKEN_VERSION_CACHE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(30),
+    )
+});
+
+static CACHE: LazyLock<TtlCache<i32, i32>> = LazyLock::new(|| TtlCache::new(*CACHE_TTL));
+
+// Bumped whenever role, password or account state changes, older access tokens are rejected.
+// None when the user no longer exists or was deactivated.
+pub async fn current_token_version(
+    pool: &web::Data<MySqlPool>,
+    user_id: i32,
+) -> Result<Option<i32>, sqlx::Error> {
+    if let Some(version) = CACHE.get(&user_id) {
+        return Ok(Some(version));
+    }
+
+    let version = sqlx::query_scalar::<_, i32>(
+        r"SELECT token_version FROM users WHERE id = ? AND is_active = TRUE",
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if let Some(version) = version {
+        CACHE.insert(user_id, version);
+    }
+
+    Ok(version)
+}
+
+// Call after bumping users.token_version so this instance stops trusting old tokens at once
+pub fn forget_token_version(user_id: i32) {
+    CACHE.remove(&user_id);
+}
@@ -0,0 +1,123 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nanoid::nanoid;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::config::totp::totp_issuer;
+use crate::utils::security::hash_token;
+
+const STEP_SECS: u64 = 30;
+// Steps of clock drift accepted on either side of the current one
+const DRIFT_STEPS: u64 = 1;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_ALPHABET: [char; 32] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm', 'n', 'p', 'q', 'r', 's', 't', 'u', 'v',
+    'w', 'x', 'y', 'z', '2', '3', '4', '5', '6', '7', '8', '9', '0',
+];
+
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+fn build_totp(secret: &str, username: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+    Some(TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP_SECS,
+        secret,
+        Some(totp_issuer().to_string()),
+        username.to_string(),
+    ))
+}
+
+// URI for authenticator apps, usually rendered as a QR code by the frontend
+pub fn otpauth_uri(secret: &str, username: &str) -> Option<String> {
+    build_totp(secret, username).map(|totp| totp.get_url())
+}
+
+// Time step of a valid code, callers must reject steps at or before the last accepted one
+// so a code can't be replayed within its drift window
+pub fn verify_code(secret: &str, username: &str, code: &str) -> Option<u64> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+    verify_code_at(secret, username, code, now)
+}
+
+fn verify_code_at(secret: &str, username: &str, code: &str, now: u64) -> Option<u64> {
+    let totp = build_totp(secret, username)?;
+    let current = now / STEP_SECS;
+    let code = code.trim();
+
+    (current.saturating_sub(DRIFT_STEPS)..=current + DRIFT_STEPS)
+        .find(|step| totp.check(code, step * STEP_SECS))
+}
+
+// Recovery codes are stored hashed, case and surrounding spaces don't matter when typed
+pub fn hash_recovery_code(code: &str) -> String {
+    hash_token(&code.trim().to_lowercase())
+}
+
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = nanoid!(10, &RECOVERY_CODE_ALPHABET);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP";
+    const NOW: u64 = 1_700_000_000;
+
+    fn code_at(time: u64) -> String {
+        build_totp(SECRET, "budi").unwrap().generate(time)
+    }
+
+    #[test]
+    fn accepts_codes_within_one_step_of_drift() {
+        let current = NOW / STEP_SECS;
+
+        assert_eq!(verify_code_at(SECRET, "budi", &code_at(NOW), NOW), Some(current));
+        assert_eq!(
+            verify_code_at(SECRET, "budi", &code_at(NOW - STEP_SECS), NOW),
+            Some(current - 1)
+        );
+        assert_eq!(
+            verify_code_at(SECRET, "budi", &format!(" {} ", code_at(NOW + STEP_SECS)), NOW),
+            Some(current + 1)
+        );
+    }
+
+    #[test]
+    fn rejects_old_and_malformed_codes() {
+        assert_eq!(verify_code_at(SECRET, "budi", &code_at(NOW - 2 * STEP_SECS), NOW), None);
+        assert_eq!(verify_code_at(SECRET, "budi", "", NOW), None);
+        assert_eq!(verify_code_at(SECRET, "budi", "abcdef", NOW), None);
+        assert_eq!(verify_code_at("not base32!", "budi", &code_at(NOW), NOW), None);
+    }
+
+    #[test]
+    fn recovery_codes_hash_the_same_however_they_are_typed() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+        for code in &codes {
+            assert_eq!(code.len(), 11);
+            assert_eq!(
+                hash_recovery_code(code),
+                hash_recovery_code(&format!(" {} ", code.to_uppercase()))
+            );
+        }
+
+        assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+        assert_eq!(hash_recovery_code(&codes[0]).len(), 64);
+    }
+}